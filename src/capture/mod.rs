@@ -0,0 +1,46 @@
+pub mod recording;
+pub mod screenshot;
+
+use crate::chip_8::display::Display;
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::palette::Palette;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+
+/// Draw the display into an image using the given palette.
+/// Every pixel of the display becomes a square of `scale` by `scale` pixels.
+pub fn render_display(display: &Display, palette: &Palette, scale: u32) -> RgbaImage {
+    let width = display.resolution.width() as u32;
+    let height = display.resolution.height() as u32;
+
+    let mut image = RgbaImage::new(width, height);
+
+    for (y, row) in display.zip_planes().into_iter().enumerate() {
+        for (x, pixel) in row.into_iter().enumerate() {
+            image.put_pixel(x as u32, y as u32, Rgba(palette.color(pixel).to_array()));
+        }
+    }
+
+    if scale > 1 {
+        imageops::resize(&image, width * scale, height * scale, FilterType::Nearest)
+    } else {
+        image
+    }
+}
+
+/// Name for captured files, made up of the ROM name and the current time
+pub fn capture_name(emulator: &Emulator) -> String {
+    let rom_name = emulator
+        .config
+        .filepath
+        .as_ref()
+        .and_then(|filepath| filepath.file_stem())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "chip_8".to_string());
+
+    format!(
+        "{}_{}",
+        rom_name,
+        chrono::Local::now().format("%Y-%m-%d_%H-%M-%S-%3f")
+    )
+}
@@ -0,0 +1,195 @@
+use crate::capture::{capture_name, render_display};
+use crate::chip_8::config::RecordingFormat;
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::sound::{Tone, SAMPLES_PER_FRAME, SAMPLE_RATE};
+use anyhow::{anyhow, Result};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+/// Records the emulated frames, and optionally the beeper, into files.
+/// Every emulated frame is recorded with a length of 1/60 of a second, regardless of the speed
+/// the emulation runs at.
+pub struct Recording {
+    output: Output,
+    audio: Option<WavWriter<BufWriter<File>>>,
+
+    /// Where the recording is saved to
+    pub path: PathBuf,
+
+    /// Amount of frames recorded so far
+    frames: u64,
+
+    /// Generates the recorded beeper, sounding like the played one
+    tone: Tone,
+}
+
+enum Output {
+    Gif {
+        encoder: GifEncoder<BufWriter<File>>,
+
+        /// The frame which is not written yet, together with the frame it started at.
+        /// Equal frames are merged into one, since GIF frames can't be shorter than 1/50 of a second
+        pending: Option<(RgbaImage, u64)>,
+    },
+
+    PngSequence,
+}
+
+impl Recording {
+    pub fn start(emulator: &Emulator) -> Result<Self> {
+        let config = &emulator.config.capture;
+        let name = capture_name(emulator);
+
+        fs::create_dir_all(&config.folder).map_err(|error| {
+            anyhow!(error).context(format!(
+                "Error creating capture folder at {}",
+                config.folder.display()
+            ))
+        })?;
+
+        let (output, path, audio_path) = match config.recording_format {
+            RecordingFormat::Gif => {
+                let path = config.folder.join(format!("{}.gif", name));
+                let file = File::create(&path).map_err(|error| {
+                    anyhow!(error).context(format!("Error creating file at {}", path.display()))
+                })?;
+
+                let mut encoder = GifEncoder::new(BufWriter::new(file));
+                encoder.set_repeat(Repeat::Infinite)?;
+
+                let output = Output::Gif {
+                    encoder,
+                    pending: None,
+                };
+
+                (output, path, config.folder.join(format!("{}.wav", name)))
+            }
+
+            RecordingFormat::PngSequence => {
+                let path = config.folder.join(name);
+                fs::create_dir_all(&path).map_err(|error| {
+                    anyhow!(error).context(format!("Error creating folder at {}", path.display()))
+                })?;
+
+                let audio_path = path.join("audio.wav");
+                (Output::PngSequence, path, audio_path)
+            }
+        };
+
+        let audio = if config.record_audio {
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate: SAMPLE_RATE,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            };
+
+            Some(WavWriter::create(&audio_path, spec).map_err(|error| {
+                anyhow!(error).context(format!("Error creating file at {}", audio_path.display()))
+            })?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            output,
+            audio,
+            path,
+            frames: 0,
+            tone: Tone::new(emulator.beeper.get_sound().clone()),
+        })
+    }
+
+    /// Record the current state of the display and beeper as the next frame
+    pub fn record_frame(&mut self, emulator: &Emulator) -> Result<()> {
+        let image = render_display(
+            &emulator.display,
+            emulator.config.active_palette(),
+            emulator.config.capture.scale,
+        );
+
+        match &mut self.output {
+            Output::Gif { encoder, pending } => match pending {
+                Some((pending_image, _)) if *pending_image == image => {}
+
+                Some((pending_image, start))
+                    if centiseconds(self.frames) - centiseconds(*start) < 2 =>
+                {
+                    // Too short to show, so only the most recent image is kept
+                    *pending_image = image;
+                }
+
+                _ => {
+                    if let Some((pending_image, start)) = pending.take() {
+                        write_gif_frame(encoder, pending_image, start, self.frames)?;
+                    }
+
+                    *pending = Some((image, self.frames));
+                }
+            },
+
+            Output::PngSequence => {
+                let filepath = self.path.join(format!("frame_{:06}.png", self.frames));
+                image.save(&filepath).map_err(|error| {
+                    anyhow!(error).context(format!("Error saving frame at {}", filepath.display()))
+                })?;
+            }
+        }
+
+        if let Some(audio) = &mut self.audio {
+            let beeping = emulator.sound_timer > 0;
+            let amplitude = emulator.beeper.get_volume() * i16::MAX as f32;
+
+            for _ in 0..SAMPLES_PER_FRAME {
+                let sample = self.tone.next_sample(beeping) * amplitude;
+                audio.write_sample(sample as i16)?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Write all remaining data and close the files
+    pub fn finish(mut self) -> Result<PathBuf> {
+        if let Output::Gif { encoder, pending } = &mut self.output {
+            if let Some((image, start)) = pending.take() {
+                write_gif_frame(encoder, image, start, self.frames.max(start + 2))?;
+            }
+        }
+
+        if let Some(audio) = self.audio.take() {
+            audio.finalize()?;
+        }
+
+        Ok(self.path)
+    }
+}
+
+fn write_gif_frame(
+    encoder: &mut GifEncoder<BufWriter<File>>,
+    image: RgbaImage,
+    start: u64,
+    end: u64,
+) -> Result<()> {
+    // Frame lengths are rounded in a way that keeps the total length of the GIF exact
+    let delay = centiseconds(end) - centiseconds(start);
+
+    encoder.encode_frame(Frame::from_parts(
+        image,
+        0,
+        0,
+        Delay::from_numer_denom_ms(delay as u32 * 10, 1),
+    ))?;
+
+    Ok(())
+}
+
+/// Convert an amount of frames into centiseconds, which is the unit GIF frame delays are stored in
+fn centiseconds(frames: u64) -> u64 {
+    frames * 100 / 60
+}
@@ -0,0 +1,34 @@
+use crate::capture::{capture_name, render_display};
+use crate::chip_8::emulator::Emulator;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::PathBuf;
+
+/// Save the current display as a PNG into the screenshot folder.
+/// Returns the path of the saved screenshot.
+pub fn save_screenshot(emulator: &Emulator) -> Result<PathBuf> {
+    let config = &emulator.config.capture;
+
+    fs::create_dir_all(&config.folder).map_err(|error| {
+        anyhow!(error).context(format!(
+            "Error creating screenshot folder at {}",
+            config.folder.display()
+        ))
+    })?;
+
+    let filepath = config
+        .folder
+        .join(format!("{}.png", capture_name(emulator)));
+
+    render_display(
+        &emulator.display,
+        emulator.config.active_palette(),
+        config.scale,
+    )
+    .save(&filepath)
+    .map_err(|error| {
+        anyhow!(error).context(format!("Error saving screenshot at {}", filepath.display()))
+    })?;
+
+    Ok(filepath)
+}
@@ -0,0 +1,125 @@
+use crate::chip_8::config::Platform;
+use crate::chip_8::emulator::{INSTRUCTIONS_START, ROM_EXTENSIONS};
+use crate::chip_8::error::EmulationError;
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// ROMs inside archives are addressed by the path of the archive, followed by the path of the ROM
+/// inside the archive, like `roms.zip/games/tetris.ch8`
+pub fn is_archive(filepath: &Path) -> bool {
+    filepath
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// The size of the largest ROM any platform can load
+pub fn max_rom_size() -> usize {
+    Platform::XoChip.memory_size() - INSTRUCTIONS_START
+}
+
+pub fn is_rom(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|extension| {
+        ROM_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str())
+    })
+}
+
+/// Split the path of a ROM inside an archive into the path of the archive and of the entry
+pub fn split_path(filepath: &Path) -> Option<(&Path, String)> {
+    let archive = filepath
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| is_archive(ancestor) && ancestor.is_file())?;
+
+    let entry = filepath
+        .strip_prefix(archive)
+        .ok()?
+        .iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    Some((archive, entry))
+}
+
+/// The names of all ROMs in the archive
+pub fn list_roms(archive: &Path) -> Result<Vec<String>> {
+    let zip = open(archive)?;
+
+    let mut roms: Vec<String> = zip
+        .file_names()
+        .filter(|name| is_rom(name))
+        .map(str::to_string)
+        .collect();
+    roms.sort();
+
+    Ok(roms)
+}
+
+/// Read a ROM from an archive. If no entry is given, the archive must hold exactly one ROM
+pub fn read_rom(archive: &Path, entry: Option<&str>) -> Result<Vec<u8>> {
+    let entry = match entry {
+        Some(entry) => entry.to_string(),
+        None => {
+            let roms = list_roms(archive)?;
+
+            match roms.as_slice() {
+                [rom] => rom.clone(),
+                [] => return Err(anyhow!("No ROMs found in {}", archive.display())),
+                _ => {
+                    return Err(anyhow!(
+                        "{} holds several ROMs, choose one of them:\n{}",
+                        archive.display(),
+                        roms.join("\n")
+                    ))
+                }
+            }
+        }
+    };
+
+    let mut zip = open(archive)?;
+    let file = zip.by_name(&entry).map_err(|error| {
+        anyhow!(error).context(format!("Error finding {} in {}", entry, archive.display()))
+    })?;
+
+    // Entries larger than the memory of any platform are rejected before they are decompressed.
+    // The size in the archive may be wrong, so reading stops past the limit too
+    let max_size = max_rom_size();
+    let too_large = |size| EmulationError::RomTooLarge { size, max_size };
+
+    if file.size() > max_size as u64 {
+        return Err(too_large(file.size() as usize).into());
+    }
+
+    let mut data = Vec::new();
+    file.take(max_size as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(|error| {
+            anyhow!(error).context(format!("Error reading {} in {}", entry, archive.display()))
+        })?;
+
+    if data.len() > max_size {
+        return Err(too_large(data.len()).into());
+    }
+
+    Ok(data)
+}
+
+/// The path of a ROM inside an archive
+pub fn rom_path(archive: &Path, entry: &str) -> PathBuf {
+    entry
+        .split('/')
+        .fold(archive.to_path_buf(), |path, part| path.join(part))
+}
+
+fn open(archive: &Path) -> Result<ZipArchive<File>> {
+    let file = File::open(archive).map_err(|error| {
+        anyhow!(error).context(format!("Error opening archive at {}", archive.display()))
+    })?;
+
+    ZipArchive::new(file).map_err(|error| {
+        anyhow!(error).context(format!("Invalid archive at {}", archive.display()))
+    })
+}
@@ -0,0 +1,254 @@
+use crate::chip_8::config::AudioOutput;
+use crate::chip_8::sound::{SAMPLES_PER_FRAME, SAMPLE_RATE};
+use anyhow::{anyhow, Result};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rodio::{OutputStream, Sink, Source};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Samples the audio device waits for after running out, before playing again.
+/// Smooths over uneven frame timing, at the cost of a little latency
+const DEVICE_PREFILL: usize = SAMPLES_PER_FRAME * 2;
+
+/// How many samples the audio device takes out of the buffer at once
+const DEVICE_CHUNK: usize = 256;
+
+/// Plays the samples of the beeper. Runs on the audio thread of the beeper
+pub trait AudioBackend {
+    fn set_volume(&mut self, volume: f32);
+
+    /// Called regularly, for backends which take the samples out of the buffer themselves
+    fn update(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Samples generated by the emulation, waiting to be played. Holds at most `capacity` samples,
+/// dropping the oldest when the emulation runs faster than the output, like in fast-forward, so
+/// the audio stays in sync with the emulation
+pub struct SampleBuffer {
+    samples: Mutex<VecDeque<f32>>,
+    capacity: usize,
+}
+
+/// Create the backend for the output, playing the samples of the buffer
+pub fn create_backend(
+    output: AudioOutput,
+    filepath: &Path,
+    buffer: Arc<SampleBuffer>,
+) -> Result<Box<dyn AudioBackend>> {
+    Ok(match output {
+        AudioOutput::Device => Box::new(RodioBackend::new(buffer)?),
+        AudioOutput::None => Box::new(NullBackend),
+        AudioOutput::WavFile => Box::new(WavBackend::new(filepath, buffer)?),
+    })
+}
+
+/// How many samples are buffered for the output at most
+pub fn buffer_capacity(output: AudioOutput) -> usize {
+    match output {
+        // About 100 ms, which keeps the latency low
+        AudioOutput::Device => SAMPLES_PER_FRAME * 6,
+        AudioOutput::None => SAMPLES_PER_FRAME,
+
+        // The file is written regularly, so no samples are dropped even at unlimited speed
+        AudioOutput::WavFile => SAMPLE_RATE as usize * 2,
+    }
+}
+
+/// Plays the beeper on the default audio device
+pub struct RodioBackend {
+    // Playback stops once the stream is dropped
+    _stream: OutputStream,
+    sink: Sink,
+}
+
+/// Takes the samples out of the buffer for the audio device, with silence while it is empty
+struct BufferSource {
+    buffer: Arc<SampleBuffer>,
+    pending: VecDeque<f32>,
+
+    /// Whether the buffer ran out, so playback waits for `DEVICE_PREFILL` samples
+    starved: bool,
+}
+
+/// Discards the beeper, for machines without audio
+pub struct NullBackend;
+
+/// Writes the beeper into a WAV file, with the exact timing of the emulation, so the audio can be
+/// checked without a device
+pub struct WavBackend {
+    writer: WavWriter<BufWriter<File>>,
+    buffer: Arc<SampleBuffer>,
+    volume: f32,
+
+    /// Samples written so far
+    written: u64,
+}
+
+impl SampleBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, samples: &[f32]) {
+        let mut buffer = self.samples.lock().unwrap();
+        buffer.extend(samples);
+
+        let excess = buffer.len().saturating_sub(self.capacity);
+        buffer.drain(..excess);
+    }
+
+    /// Move up to `count` samples into `output`, oldest first
+    pub fn take(&self, output: &mut VecDeque<f32>, count: usize) {
+        let mut buffer = self.samples.lock().unwrap();
+        let count = count.min(buffer.len());
+        output.extend(buffer.drain(..count));
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.samples.lock().unwrap().clear();
+    }
+}
+
+impl RodioBackend {
+    pub fn new(buffer: Arc<SampleBuffer>) -> Result<Self> {
+        let (stream, stream_handle) = OutputStream::try_default()
+            .map_err(|error| anyhow!(error).context("Error opening the audio device"))?;
+        let sink = Sink::try_new(&stream_handle)
+            .map_err(|error| anyhow!(error).context("Error playing on the audio device"))?;
+
+        sink.append(BufferSource {
+            buffer,
+            pending: VecDeque::with_capacity(DEVICE_CHUNK),
+            starved: true,
+        });
+
+        Ok(Self {
+            _stream: stream,
+            sink,
+        })
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    fn set_volume(&mut self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+}
+
+impl Iterator for BufferSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_empty() {
+            if self.starved && self.buffer.len() < DEVICE_PREFILL {
+                return Some(0f32);
+            }
+
+            self.buffer.take(&mut self.pending, DEVICE_CHUNK);
+            self.starved = self.pending.is_empty();
+        }
+
+        Some(self.pending.pop_front().unwrap_or(0f32))
+    }
+}
+
+impl Source for BufferSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn set_volume(&mut self, _volume: f32) {}
+}
+
+impl WavBackend {
+    pub fn new(filepath: &Path, buffer: Arc<SampleBuffer>) -> Result<Self> {
+        if let Some(folder) = filepath
+            .parent()
+            .filter(|folder| !folder.as_os_str().is_empty())
+        {
+            fs::create_dir_all(folder).map_err(|error| {
+                anyhow!(error).context(format!("Error creating folder at {}", folder.display()))
+            })?;
+        }
+
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let writer = WavWriter::create(filepath, spec).map_err(|error| {
+            anyhow!(error).context(format!(
+                "Error creating audio file at {}",
+                filepath.display()
+            ))
+        })?;
+
+        Ok(Self {
+            writer,
+            buffer,
+            volume: 0f32,
+            written: 0,
+        })
+    }
+}
+
+impl AudioBackend for WavBackend {
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    fn update(&mut self) -> Result<()> {
+        let mut samples = VecDeque::new();
+        self.buffer.take(&mut samples, usize::MAX);
+
+        let amplitude = self.volume * i16::MAX as f32;
+        let previous_seconds = self.written / SAMPLE_RATE as u64;
+
+        for sample in &samples {
+            self.writer.write_sample((sample * amplitude) as i16)?;
+        }
+
+        self.written += samples.len() as u64;
+
+        // Keep the file valid about once a second, since it is never finalized if the emulator
+        // is closed
+        if self.written / SAMPLE_RATE as u64 != previous_seconds {
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
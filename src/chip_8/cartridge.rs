@@ -0,0 +1,162 @@
+use crate::chip_8::config::Timing;
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::octo;
+use crate::chip_8::palette::Palette;
+use anyhow::{anyhow, Result};
+use gif::{ColorOutput, DecodeOptions};
+use serde::Deserialize;
+
+/// An Octo cartridge, which is a GIF with an Octo program and its options hidden in the image.
+/// Every byte is spread over the lowest 2 bits of the color indices of 4 pixels, most significant
+/// bits first, continuing through all frames. The first 4 bytes are the big endian length of a
+/// JSON object, which holds the program source and the options
+pub struct Cartridge {
+    /// The compiled program
+    pub rom: Vec<u8>,
+
+    pub options: OctoOptions,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    program: String,
+
+    #[serde(default)]
+    options: OctoOptions,
+}
+
+/// Emulation settings of an Octo program. Settings Octo has, but this emulator doesn't, are ignored
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OctoOptions {
+    pub tickrate: Option<u32>,
+    pub background_color: Option<String>,
+    pub fill_color: Option<String>,
+    pub fill_color2: Option<String>,
+    pub blend_color: Option<String>,
+    pub shift_quirks: Option<bool>,
+    pub load_store_quirks: Option<bool>,
+    pub jump_quirks: Option<bool>,
+    pub logic_quirks: Option<bool>,
+    pub clip_quirks: Option<bool>,
+
+    /// Maximum size of the program in bytes, which tells the platform it was written for
+    pub max_size: Option<usize>,
+}
+
+impl Cartridge {
+    pub fn is_cartridge(data: &[u8]) -> bool {
+        data.starts_with(b"GIF8")
+    }
+
+    /// Extract the program from the cartridge and compile it
+    pub fn load(data: &[u8]) -> Result<Self> {
+        let payload = extract_payload(data)?;
+        let payload: Payload = serde_json::from_slice(&payload)
+            .map_err(|error| anyhow!(error).context("Invalid data in Octo cartridge"))?;
+
+        Ok(Self {
+            rom: octo::compile(&payload.program)?,
+            options: payload.options,
+        })
+    }
+}
+
+impl OctoOptions {
+    pub fn apply(&self, emulator: &mut Emulator) {
+        let config = &mut emulator.config;
+        let quirks = &mut config.quirks;
+
+        if let Some(tickrate) = self.tickrate {
+            config.timing = Timing::InstructionsPerFrame;
+            config.instructions_per_frame = tickrate;
+        }
+
+        // Some of Octo's quirks are the opposite of ours
+        if let Some(shift) = self.shift_quirks {
+            quirks.shift_vx_directly = shift;
+        }
+
+        if let Some(load_store) = self.load_store_quirks {
+            quirks.increment_i_reg = !load_store;
+        }
+
+        if let Some(jump) = self.jump_quirks {
+            quirks.vx_offset_jump = jump;
+        }
+
+        if let Some(logic) = self.logic_quirks {
+            quirks.vf_reset = logic;
+        }
+
+        if let Some(clip) = self.clip_quirks {
+            quirks.wrap_sprites = !clip;
+        }
+
+        if let Some(max_size) = self.max_size {
+            let memory_size = if max_size > 3584 { 65536 } else { 4096 };
+            emulator.memory.resize(memory_size);
+        }
+
+        if let (Some(palette), Some(rom_name)) = (self.palette(), config.rom_name()) {
+            // A palette chosen by the user for this ROM takes precedence
+            config.rom_palettes.entry(rom_name).or_insert(palette);
+        }
+    }
+
+    fn palette(&self) -> Option<Palette> {
+        let colors = [
+            &self.background_color,
+            &self.fill_color,
+            &self.fill_color2,
+            &self.blend_color,
+        ];
+
+        if colors.iter().all(|color| color.is_none()) {
+            return None;
+        }
+
+        let mut palette = Palette::default();
+
+        for (color, text) in palette.colors.iter_mut().zip(colors) {
+            if let Some(parsed) = text
+                .as_deref()
+                .and_then(|text| Palette::parse_color(text).ok())
+            {
+                *color = parsed;
+            }
+        }
+
+        Some(palette)
+    }
+}
+
+fn extract_payload(data: &[u8]) -> Result<Vec<u8>> {
+    let mut options = DecodeOptions::new();
+    options.set_color_output(ColorOutput::Indexed);
+
+    let mut decoder = options
+        .read_info(data)
+        .map_err(|error| anyhow!(error).context("Invalid Octo cartridge image"))?;
+
+    let mut bytes = Vec::new();
+
+    while let Some(frame) = decoder
+        .read_next_frame()
+        .map_err(|error| anyhow!(error).context("Invalid Octo cartridge image"))?
+    {
+        for pixels in frame.buffer.chunks_exact(4) {
+            bytes.push(pixels.iter().fold(0, |byte, pixel| byte << 2 | pixel & 3));
+        }
+    }
+
+    let length = bytes
+        .get(0..4)
+        .map(|length| u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize)
+        .ok_or_else(|| anyhow!("Octo cartridge is empty"))?;
+
+    bytes
+        .get(4..4 + length)
+        .map(|payload| payload.to_vec())
+        .ok_or_else(|| anyhow!("Octo cartridge is incomplete"))
+}
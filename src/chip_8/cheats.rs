@@ -0,0 +1,112 @@
+use crate::chip_8::memory::Memory;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Sets a byte of memory to a value at the start of every frame while enabled, which freezes it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cheat {
+    pub description: String,
+    pub address: usize,
+    pub value: u8,
+    pub enabled: bool,
+}
+
+/// Finds the address of a variable, by narrowing down the addresses whose values changed the same
+/// way as the variable between two points in time, like lives which decreased
+pub struct CheatSearch {
+    /// Memory at the last search, which the next search compares against
+    previous: Vec<u8>,
+
+    /// Addresses matching all searches so far
+    pub candidates: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SearchFilter {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(u8),
+}
+
+impl Cheat {
+    pub fn new(address: usize, value: u8) -> Self {
+        Self {
+            description: String::new(),
+            address,
+            value,
+            enabled: true,
+        }
+    }
+
+    /// Write the value into memory
+    pub fn apply(&self, memory: &mut Memory) {
+        if let Some(byte) = memory.data.get_mut(self.address) {
+            *byte = self.value;
+        }
+    }
+}
+
+impl CheatSearch {
+    /// Start a search with all addresses as candidates
+    pub fn start(memory: &Memory) -> Self {
+        Self {
+            previous: memory.data.clone(),
+            candidates: (0..memory.size).collect(),
+        }
+    }
+
+    /// Keep the candidates matching the filter, comparing the memory against the last search
+    pub fn filter(&mut self, memory: &Memory, filter: SearchFilter) {
+        self.candidates.retain(|address| {
+            let (Some(previous), Some(current)) =
+                (self.previous.get(*address), memory.data.get(*address))
+            else {
+                return false;
+            };
+
+            match filter {
+                SearchFilter::Changed => current != previous,
+                SearchFilter::Unchanged => current == previous,
+                SearchFilter::Increased => current > previous,
+                SearchFilter::Decreased => current < previous,
+                SearchFilter::Equals(value) => *current == value,
+            }
+        });
+
+        self.previous = memory.data.clone();
+    }
+
+    /// Whether the search was started on memory of the same size
+    pub fn matches(&self, memory: &Memory) -> bool {
+        self.previous.len() == memory.data.len()
+    }
+
+    /// The value of the address at the last search
+    pub fn previous_value(&self, address: usize) -> Option<u8> {
+        self.previous.get(address).copied()
+    }
+}
+
+impl SearchFilter {
+    /// All filters comparing against the last search
+    pub const COMPARISONS: [SearchFilter; 4] = [
+        SearchFilter::Changed,
+        SearchFilter::Unchanged,
+        SearchFilter::Increased,
+        SearchFilter::Decreased,
+    ];
+}
+
+impl Display for SearchFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchFilter::Changed => write!(f, "Changed"),
+            SearchFilter::Unchanged => write!(f, "Unchanged"),
+            SearchFilter::Increased => write!(f, "Increased"),
+            SearchFilter::Decreased => write!(f, "Decreased"),
+            SearchFilter::Equals(value) => write!(f, "Equals {}", value),
+        }
+    }
+}
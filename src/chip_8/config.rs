@@ -1,23 +1,130 @@
-use std::path::PathBuf;
+use crate::chip_8::cheats::Cheat;
+use crate::chip_8::palette::Palette;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    // Saved separately as the global emulation settings, see `SavedSettings`
+    #[serde(skip)]
     pub instructions_per_frame: u32,
+    #[serde(skip)]
+    pub instructions_per_second: u32,
+    #[serde(skip)]
+    pub timing: Timing,
+
+    pub speed: Speed,
+    pub fast_forward_speed: Speed,
     pub filepath: Option<PathBuf>,
+    #[serde(skip)]
     pub quirks: Quirks,
+    pub palette: Palette,
+    pub anti_flicker: AntiFlicker,
+    pub scaling: Scaling,
+    pub fullscreen: bool,
+
+    /// Show a clickable Chip 8 keypad on top of the display
+    pub show_keypad: bool,
+
+    pub capture: CaptureConfig,
+    pub sound: SoundConfig,
+
+    /// Palettes used instead of the default palette for specific ROMs, keyed by the ROM file name
+    pub rom_palettes: HashMap<String, Palette>,
+
+    /// Configure known ROMs automatically from the bundled ROM database when they are loaded
+    pub use_rom_database: bool,
+
+    /// Emulation settings used instead of the global ones for specific ROMs, keyed by the ROM
+    /// file name
+    pub rom_overrides: HashMap<String, EmulationSettings>,
+
+    /// The global emulation settings, while the loaded ROM uses settings of its own
+    #[serde(skip)]
+    pub global_emulation: Option<EmulationSettings>,
+
+    /// Palette given on the command line, used for all ROMs in this session instead of the
+    /// saved palettes
+    #[serde(skip)]
+    pub palette_override: Option<Palette>,
+
+    /// Emulation options given on the command line, applied on top of the settings of every ROM
+    /// loaded in this session
+    #[serde(skip)]
+    pub emulation_overrides: EmulationOverrides,
+
+    /// Cheats of specific ROMs, keyed by the ROM file name
+    pub cheats: HashMap<String, Vec<Cheat>>,
+
+    pub library: LibraryConfig,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             instructions_per_frame: 10,
+            instructions_per_second: 600,
+            timing: Timing::default(),
+            speed: Speed::default(),
+            fast_forward_speed: Speed::Unlimited,
             filepath: None,
             quirks: Quirks::default(),
+            palette: Palette::default(),
+            anti_flicker: AntiFlicker::default(),
+            scaling: Scaling::default(),
+            fullscreen: false,
+            show_keypad: false,
+            capture: CaptureConfig::default(),
+            sound: SoundConfig::default(),
+            rom_palettes: HashMap::new(),
+            use_rom_database: true,
+            rom_overrides: HashMap::new(),
+            global_emulation: None,
+            palette_override: None,
+            emulation_overrides: EmulationOverrides::default(),
+            cheats: HashMap::new(),
+            library: LibraryConfig::default(),
         }
     }
 }
 
-#[derive(Copy, Clone, Default)]
+impl Config {
+    /// The file name of the selected ROM
+    pub fn rom_name(&self) -> Option<String> {
+        self.filepath
+            .as_ref()
+            .and_then(|filepath| filepath.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+
+    /// The palette for the selected ROM, falling back to the default palette
+    pub fn active_palette(&self) -> &Palette {
+        if let Some(palette) = &self.palette_override {
+            return palette;
+        }
+
+        self.rom_name()
+            .and_then(|rom_name| self.rom_palettes.get(&rom_name))
+            .unwrap_or(&self.palette)
+    }
+
+    pub fn active_palette_mut(&mut self) -> &mut Palette {
+        let rom_name = self.rom_name();
+
+        match (&mut self.palette_override, rom_name) {
+            (Some(palette), _) => palette,
+            (None, Some(rom_name)) if self.rom_palettes.contains_key(&rom_name) => {
+                self.rom_palettes.get_mut(&rom_name).unwrap()
+            }
+            _ => &mut self.palette,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Quirks {
     pub vf_reset: bool,
     pub increment_i_reg: bool,
@@ -25,3 +132,472 @@ pub struct Quirks {
     pub shift_vx_directly: bool,
     pub wrap_sprites: bool,
 }
+
+/// The settings which change how programs are emulated
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmulationSettings {
+    pub timing: Timing,
+    pub instructions_per_frame: u32,
+    pub instructions_per_second: u32,
+    pub quirks: Quirks,
+    pub memory_size: usize,
+}
+
+impl Default for EmulationSettings {
+    fn default() -> Self {
+        let config = Config::default();
+
+        Self {
+            timing: config.timing,
+            instructions_per_frame: config.instructions_per_frame,
+            instructions_per_second: config.instructions_per_second,
+            quirks: config.quirks,
+            memory_size: 4096,
+        }
+    }
+}
+
+/// Emulation settings forced for a whole session, where each unset option keeps the setting of
+/// the loaded ROM
+#[derive(Copy, Clone, Default)]
+pub struct EmulationOverrides {
+    pub platform: Option<Platform>,
+    pub vf_reset: Option<bool>,
+    pub increment_i_reg: Option<bool>,
+    pub vx_offset_jump: Option<bool>,
+    pub shift_vx_directly: Option<bool>,
+    pub wrap_sprites: Option<bool>,
+    pub instructions_per_frame: Option<u32>,
+}
+
+/// Chip 8 variants, each with the quirks, memory size and speed its programs expect
+#[derive(Copy, Clone, PartialEq)]
+pub enum Platform {
+    /// The original interpreter on the COSMAC VIP
+    Chip8,
+
+    /// Chip 8 as implemented by most modern interpreters
+    ModernChip8,
+
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 4] = [
+        Platform::Chip8,
+        Platform::ModernChip8,
+        Platform::SuperChip,
+        Platform::XoChip,
+    ];
+
+    /// Get the platform from its id in the ROM database
+    pub fn from_database_id(id: &str) -> Option<Self> {
+        match id {
+            "originalChip8" | "hybridVIP" => Some(Platform::Chip8),
+            "modernChip8" => Some(Platform::ModernChip8),
+            "chip48" | "superchip1" | "superchip" => Some(Platform::SuperChip),
+            "xochip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks {
+                vf_reset: true,
+                increment_i_reg: true,
+                ..Default::default()
+            },
+            Platform::ModernChip8 => Quirks {
+                increment_i_reg: true,
+                ..Default::default()
+            },
+            Platform::SuperChip => Quirks {
+                vx_offset_jump: true,
+                shift_vx_directly: true,
+                ..Default::default()
+            },
+            Platform::XoChip => Quirks {
+                increment_i_reg: true,
+                wrap_sprites: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::XoChip => 65536,
+            _ => 4096,
+        }
+    }
+
+    /// The usual amount of instructions per frame for programs of this platform
+    pub fn instructions_per_frame(&self) -> u32 {
+        match self {
+            Platform::Chip8 => 15,
+            Platform::ModernChip8 => 12,
+            Platform::SuperChip => 30,
+            Platform::XoChip => 1000,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Chip8 => write!(f, "Chip 8 (COSMAC VIP)"),
+            Platform::ModernChip8 => write!(f, "Modern Chip 8"),
+            Platform::SuperChip => write!(f, "SuperChip"),
+            Platform::XoChip => write!(f, "XO-Chip"),
+        }
+    }
+}
+
+/// Decides how many instructions are executed in every emulated frame
+#[derive(Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum Timing {
+    /// Execute a fixed amount of instructions every frame
+    #[default]
+    InstructionsPerFrame,
+
+    /// Spread a fixed amount of instructions per second evenly across the 60 frames of a second
+    InstructionsPerSecond,
+}
+
+/// How fast emulated time passes compared to real time
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Speed {
+    Multiplier(f32),
+
+    /// Emulate as many frames as possible, without any FPS limiting
+    Unlimited,
+}
+
+impl Speed {
+    pub const PRESETS: [Speed; 7] = [
+        Speed::Multiplier(0.25),
+        Speed::Multiplier(0.5),
+        Speed::Multiplier(1.0),
+        Speed::Multiplier(2.0),
+        Speed::Multiplier(4.0),
+        Speed::Multiplier(8.0),
+        Speed::Unlimited,
+    ];
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::Multiplier(1.0)
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Speed::Multiplier(multiplier) => write!(f, "{}x", multiplier),
+            Speed::Unlimited => write!(f, "Unlimited"),
+        }
+    }
+}
+
+/// Post-processing of the display, which reduces the flicker caused by sprites being erased and
+/// redrawn using XOR
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AntiFlicker {
+    pub mode: AntiFlickerMode,
+
+    /// How many of the most recent frames are blended together
+    pub blend_frames: usize,
+
+    /// How much of its brightness a pixel keeps every frame after turning off
+    pub persistence: f32,
+}
+
+impl Default for AntiFlicker {
+    fn default() -> Self {
+        Self {
+            mode: AntiFlickerMode::default(),
+            blend_frames: 3,
+            persistence: 0.6,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum AntiFlickerMode {
+    #[default]
+    Off,
+
+    /// Show the average of the most recent frames
+    FrameBlending,
+
+    /// Let pixels fade out slowly, like on a phosphor screen
+    PhosphorDecay,
+
+    /// Keep showing the previous frame while the program is still redrawing erased sprites
+    WaitForRedraw,
+}
+
+impl fmt::Display for AntiFlickerMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AntiFlickerMode::Off => write!(f, "Off"),
+            AntiFlickerMode::FrameBlending => write!(f, "Frame Blending"),
+            AntiFlickerMode::PhosphorDecay => write!(f, "Phosphor Decay"),
+            AntiFlickerMode::WaitForRedraw => write!(f, "Wait for Redraw"),
+        }
+    }
+}
+
+/// How the display is scaled to fit the window
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scaling {
+    pub mode: ScalingMode,
+
+    /// Draw lines between the pixels
+    pub pixel_grid: bool,
+
+    /// Darken every pixel row at the bottom, like the scanlines of a CRT screen
+    pub scanlines: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum ScalingMode {
+    /// Stretch the display over the whole window
+    Stretch,
+
+    /// Scale the display as large as possible, while keeping its aspect ratio
+    #[default]
+    KeepAspect,
+
+    /// Scale the display by the largest whole number which fits, so all pixels have the same size
+    Integer,
+}
+
+impl fmt::Display for ScalingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalingMode::Stretch => write!(f, "Stretch"),
+            ScalingMode::KeepAspect => write!(f, "Keep Aspect Ratio"),
+            ScalingMode::Integer => write!(f, "Integer Scaling"),
+        }
+    }
+}
+
+/// Settings for screenshots and recordings
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// The folder screenshots and recordings are saved into
+    pub folder: PathBuf,
+
+    /// Every pixel of the display becomes a square of `scale` by `scale` pixels.
+    /// A scale of 1 captures at the native resolution
+    pub scale: u32,
+
+    pub recording_format: RecordingFormat,
+
+    /// Record the beeper into a WAV file alongside the recording
+    pub record_audio: bool,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            folder: PathBuf::from("captures"),
+            scale: 1,
+            recording_format: RecordingFormat::default(),
+            record_audio: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum RecordingFormat {
+    /// An animated GIF
+    #[default]
+    Gif,
+
+    /// A folder with one numbered PNG per frame
+    PngSequence,
+}
+
+impl fmt::Display for RecordingFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingFormat::Gif => write!(f, "Animated GIF"),
+            RecordingFormat::PngSequence => write!(f, "PNG Sequence"),
+        }
+    }
+}
+
+/// The sound of the beeper
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundConfig {
+    /// Frequency of the tone in Hz
+    pub frequency: f32,
+
+    pub waveform: Waveform,
+
+    /// How long the beeper takes to fade in and out when switched on and off, in milliseconds.
+    /// Fading prevents clicks
+    pub attack: u32,
+    pub release: u32,
+
+    /// A WAV file which is looped instead of playing the waveform
+    pub sample: Option<PathBuf>,
+
+    /// Where the beeper is played
+    pub output: AudioOutput,
+
+    /// The WAV file the beeper is written into, for `AudioOutput::WavFile`
+    pub output_file: PathBuf,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            waveform: Waveform::default(),
+            attack: 5,
+            release: 5,
+            sample: None,
+            output: AudioOutput::default(),
+            output_file: PathBuf::from("captures/beeper.wav"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum Waveform {
+    Square,
+    #[default]
+    Sine,
+    Triangle,
+
+    /// White noise, which changes its value at the frequency of the tone
+    Noise,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 4] = [
+        Waveform::Square,
+        Waveform::Sine,
+        Waveform::Triangle,
+        Waveform::Noise,
+    ];
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Waveform::Square => write!(f, "Square"),
+            Waveform::Sine => write!(f, "Sine"),
+            Waveform::Triangle => write!(f, "Triangle"),
+            Waveform::Noise => write!(f, "Noise"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum AudioOutput {
+    /// The default audio device
+    #[default]
+    Device,
+
+    /// No audio, for machines without an audio device
+    None,
+
+    /// A WAV file, written in real time
+    WavFile,
+}
+
+impl AudioOutput {
+    pub const ALL: [AudioOutput; 3] =
+        [AudioOutput::Device, AudioOutput::None, AudioOutput::WavFile];
+}
+
+impl fmt::Display for AudioOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioOutput::Device => write!(f, "Audio Device"),
+            AudioOutput::None => write!(f, "None"),
+            AudioOutput::WavFile => write!(f, "WAV File"),
+        }
+    }
+}
+
+/// Folders the ROM library is built from, together with favourite and played ROMs
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibraryConfig {
+    pub folders: Vec<PathBuf>,
+    pub favourites: Vec<PathBuf>,
+
+    /// Played ROMs, most recently played first
+    pub played: Vec<PlayedRom>,
+}
+
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        Self {
+            folders: ["roms/games", "roms/tests", "roms/others"]
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+            favourites: Vec::new(),
+            played: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayedRom {
+    pub filepath: PathBuf,
+
+    /// Unix timestamp in seconds
+    pub last_played: i64,
+}
+
+impl LibraryConfig {
+    /// How many played ROMs are remembered
+    const MAX_PLAYED: usize = 100;
+
+    pub fn mark_played(&mut self, filepath: &Path, timestamp: i64) {
+        self.played.retain(|rom| rom.filepath != filepath);
+        self.played.insert(
+            0,
+            PlayedRom {
+                filepath: filepath.to_path_buf(),
+                last_played: timestamp,
+            },
+        );
+        self.played.truncate(Self::MAX_PLAYED);
+    }
+
+    pub fn last_played(&self, filepath: &Path) -> Option<i64> {
+        self.played
+            .iter()
+            .find(|rom| rom.filepath == filepath)
+            .map(|rom| rom.last_played)
+    }
+
+    pub fn is_favourite(&self, filepath: &Path) -> bool {
+        self.favourites
+            .iter()
+            .any(|favourite| favourite == filepath)
+    }
+
+    pub fn toggle_favourite(&mut self, filepath: &Path) {
+        if self.is_favourite(filepath) {
+            self.favourites.retain(|favourite| favourite != filepath);
+        } else {
+            self.favourites.push(filepath.to_path_buf());
+        }
+    }
+}
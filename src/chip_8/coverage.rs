@@ -0,0 +1,160 @@
+use crate::chip_8::emulator::INSTRUCTIONS_START;
+use crate::chip_8::memory::{AccessKind, MemoryAccess};
+use anyhow::{anyhow, Result};
+use std::fmt::{Display, Formatter, Write as _};
+use std::fs;
+use std::path::Path;
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+const WRITTEN: u8 = 4;
+
+/// Records for every byte of memory whether it was executed, read or written, to find the parts
+/// of a ROM which were never reached and which bytes are data
+#[derive(Clone)]
+pub struct Coverage {
+    /// Flags of each byte of memory
+    flags: Vec<u8>,
+}
+
+/// How a byte was used, from the flags recorded for it
+#[derive(Clone, Copy, PartialEq)]
+pub enum ByteUse {
+    Unused,
+    Code,
+    Data,
+    Written,
+
+    /// Executed and written, so the ROM modifies its own code
+    SelfModifyingCode,
+}
+
+impl Coverage {
+    pub fn new(memory_size: usize) -> Self {
+        Self {
+            flags: vec![0; memory_size],
+        }
+    }
+
+    /// Record the execution of the instruction at the address, of `length` bytes
+    pub fn record_execution(&mut self, address: usize, length: usize) {
+        for address in address..address + length {
+            self.set_flag(address, EXECUTED);
+        }
+    }
+
+    pub fn record_accesses(&mut self, accesses: &[MemoryAccess]) {
+        for access in accesses {
+            let flag = match access.kind {
+                AccessKind::Read => READ,
+                AccessKind::Write => WRITTEN,
+            };
+
+            self.set_flag(access.address, flag);
+        }
+    }
+
+    pub fn byte_use(&self, address: usize) -> ByteUse {
+        let flags = self.flags.get(address).copied().unwrap_or(0);
+
+        if flags & EXECUTED != 0 {
+            if flags & WRITTEN != 0 {
+                ByteUse::SelfModifyingCode
+            } else {
+                ByteUse::Code
+            }
+        } else if flags & WRITTEN != 0 {
+            ByteUse::Written
+        } else if flags & READ != 0 {
+            ByteUse::Data
+        } else {
+            ByteUse::Unused
+        }
+    }
+
+    /// How many bytes of the range were used each way, in the order of `ByteUse::ALL`
+    pub fn summary(&self, start: usize, end: usize) -> [usize; 5] {
+        let mut counts = [0; 5];
+
+        for address in start..end {
+            let byte_use = self.byte_use(address);
+            counts[ByteUse::ALL.iter().position(|u| *u == byte_use).unwrap()] += 1;
+        }
+
+        counts
+    }
+
+    /// Export a report of the ROM loaded at `INSTRUCTIONS_START`, with a summary and the ranges of
+    /// bytes used the same way
+    pub fn export_report(&self, filepath: &Path, rom_size: usize) -> Result<()> {
+        let (start, end) = (INSTRUCTIONS_START, INSTRUCTIONS_START + rom_size);
+        let mut report = String::from("start,end,bytes,use\n");
+
+        let mut range_start = start;
+        for address in start..=end {
+            if address == end || self.byte_use(address) != self.byte_use(range_start) {
+                if address > range_start {
+                    let _ = writeln!(
+                        report,
+                        "{:#05X},{:#05X},{},{}",
+                        range_start,
+                        address - 1,
+                        address - range_start,
+                        self.byte_use(range_start)
+                    );
+                }
+
+                range_start = address;
+            }
+        }
+
+        report.push_str("\nuse,bytes,share\n");
+
+        for (byte_use, count) in ByteUse::ALL.iter().zip(self.summary(start, end)) {
+            let _ = writeln!(
+                report,
+                "{},{},{:.1}%",
+                byte_use,
+                count,
+                count as f64 / rom_size.max(1) as f64 * 100.0
+            );
+        }
+
+        fs::write(filepath, report).map_err(|error| {
+            anyhow!(error).context(format!(
+                "Error exporting coverage report to {}",
+                filepath.display()
+            ))
+        })
+    }
+
+    fn set_flag(&mut self, address: usize, flag: u8) {
+        if address >= self.flags.len() {
+            self.flags.resize(address + 1, 0);
+        }
+
+        self.flags[address] |= flag;
+    }
+}
+
+impl ByteUse {
+    pub const ALL: [ByteUse; 5] = [
+        ByteUse::Code,
+        ByteUse::Data,
+        ByteUse::Written,
+        ByteUse::SelfModifyingCode,
+        ByteUse::Unused,
+    ];
+}
+
+impl Display for ByteUse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ByteUse::Unused => write!(f, "Unused"),
+            ByteUse::Code => write!(f, "Code"),
+            ByteUse::Data => write!(f, "Data"),
+            ByteUse::Written => write!(f, "Written"),
+            ByteUse::SelfModifyingCode => write!(f, "Self-modifying code"),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::expression::{Expression, Template};
+use crate::chip_8::memory::{AccessKind, MemoryAccess};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::mem;
+
+/// How many messages of tracepoints are kept in the trace log
+const MAX_TRACE_LOG: usize = 1000;
+
+/// Breakpoints and watchpoints, which pause the emulation and open the debugger
+#[derive(Clone, Default)]
+pub struct Debugger {
+    /// Breakpoints by the address of the instruction they break at, before it is executed
+    pub breakpoints: BTreeMap<usize, Breakpoint>,
+
+    pub watchpoints: Vec<Watchpoint>,
+
+    /// Messages logged by tracepoints, oldest first
+    pub trace_log: VecDeque<String>,
+
+    /// Why the emulation was paused last
+    pub break_reason: Option<String>,
+
+    /// Don't break at a breakpoint at the next instruction, so emulation can continue from it
+    skip_breakpoint: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct Breakpoint {
+    /// Only break when the condition is true
+    pub condition: Option<Expression>,
+
+    /// Only break once the breakpoint was hit this many times
+    pub hit_target: Option<u64>,
+
+    /// How often the breakpoint was hit with its condition true
+    pub hits: u64,
+
+    /// Log this message instead of breaking, which makes the breakpoint a tracepoint
+    pub log_message: Option<Template>,
+}
+
+/// Breaks after an instruction accessed memory in the range
+#[derive(Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    pub start: usize,
+
+    /// Last address of the range, inclusive
+    pub end: usize,
+
+    pub kind: WatchKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+
+    /// Reads and writes
+    Access,
+}
+
+impl Debugger {
+    /// Continue the emulation from the current instruction, without breaking at it again
+    pub fn resume(&mut self) {
+        self.skip_breakpoint = true;
+        self.break_reason = None;
+    }
+
+    pub fn toggle_breakpoint(&mut self, address: usize) {
+        if self.breakpoints.remove(&address).is_none() {
+            self.breakpoints.insert(address, Breakpoint::default());
+        }
+    }
+
+    /// Whether `check_breakpoint` has to be called before the instruction at the address
+    pub fn should_check(&self, pc: usize) -> bool {
+        self.skip_breakpoint || self.breakpoints.contains_key(&pc)
+    }
+
+    /// Check for a breakpoint at the instruction about to be executed, and log the message of a
+    /// tracepoint
+    pub fn check_breakpoint(&mut self, emulator: &Emulator) -> bool {
+        let pc = emulator.pc;
+
+        if mem::take(&mut self.skip_breakpoint) {
+            return false;
+        }
+
+        let Some(breakpoint) = self.breakpoints.get_mut(&pc) else {
+            return false;
+        };
+
+        if let Some(condition) = &breakpoint.condition {
+            if !condition.is_true(emulator) {
+                return false;
+            }
+        }
+
+        breakpoint.hits += 1;
+
+        if breakpoint
+            .hit_target
+            .is_some_and(|target| breakpoint.hits < target)
+        {
+            return false;
+        }
+
+        if let Some(message) = &breakpoint.log_message {
+            let message = message.format(emulator);
+
+            if self.trace_log.len() == MAX_TRACE_LOG {
+                self.trace_log.pop_front();
+            }
+            self.trace_log.push_back(message);
+
+            return false;
+        }
+
+        self.break_reason = Some(match &breakpoint.condition {
+            Some(condition) => format!("Breakpoint at {:#05X}, {} is true", pc, condition),
+            None => format!("Breakpoint at {:#05X}", pc),
+        });
+
+        true
+    }
+
+    /// Check the memory accesses of the instruction at the address against the watchpoints
+    pub fn check_watchpoints(&mut self, pc: usize, accesses: &[MemoryAccess]) -> bool {
+        let triggered = accesses.iter().find(|access| {
+            self.watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.is_triggered_by(access))
+        });
+
+        let Some(access) = triggered else {
+            return false;
+        };
+
+        self.break_reason = Some(format!(
+            "{} of {:#05X} by instruction at {:#05X}",
+            match access.kind {
+                AccessKind::Read => "Read",
+                AccessKind::Write => "Write",
+            },
+            access.address,
+            pc
+        ));
+
+        true
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            if self.log_message.is_some() {
+                "Trace"
+            } else {
+                "Break"
+            }
+        )?;
+
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition)?;
+        }
+
+        match self.hit_target {
+            Some(target) => write!(f, ", hit {} / {}", self.hits, target)?,
+            None => write!(f, ", hit {}", self.hits)?,
+        }
+
+        if let Some(message) = &self.log_message {
+            write!(f, ": {}", message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Watchpoint {
+    fn is_triggered_by(&self, access: &MemoryAccess) -> bool {
+        let kind_matches = match self.kind {
+            WatchKind::Read => access.kind == AccessKind::Read,
+            WatchKind::Write => access.kind == AccessKind::Write,
+            WatchKind::Access => true,
+        };
+
+        kind_matches && (self.start..=self.end).contains(&access.address)
+    }
+}
+
+impl Display for Watchpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{} {:#05X}", self.kind, self.start)
+        } else {
+            write!(f, "{} {:#05X} - {:#05X}", self.kind, self.start, self.end)
+        }
+    }
+}
+
+impl WatchKind {
+    pub const ALL: [WatchKind; 3] = [WatchKind::Read, WatchKind::Write, WatchKind::Access];
+}
+
+impl Display for WatchKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchKind::Read => write!(f, "Read"),
+            WatchKind::Write => write!(f, "Write"),
+            WatchKind::Access => write!(f, "Access"),
+        }
+    }
+}
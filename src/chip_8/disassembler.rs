@@ -0,0 +1,108 @@
+/// Disassemble the instruction at the address into its mnemonic, using the common notation of
+/// Cowgod's Chip 8 reference, extended for SuperChip and XO-Chip.
+/// Returns the text and the size of the instruction in bytes, which is 4 for F000 and 2 otherwise
+pub fn disassemble(data: &[u8], address: usize) -> (String, usize) {
+    let byte = |address: usize| data.get(address).copied().unwrap_or(0) as u16;
+    let opcode = byte(address) << 8 | byte(address + 1);
+
+    if opcode == 0xF000 {
+        let long = byte(address + 2) << 8 | byte(address + 3);
+        return (format!("LD I, {:#06X}", long), 4);
+    }
+
+    (disassemble_opcode(opcode), 2)
+}
+
+/// Disassemble a single opcode. F000 is shown without the address following it
+pub fn disassemble_opcode(opcode: u16) -> String {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    let nnn = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            0x00FB => "SCR".to_string(),
+            0x00FC => "SCL".to_string(),
+            0x00FD => "EXIT".to_string(),
+            0x00FE => "LOW".to_string(),
+            0x00FF => "HIGH".to_string(),
+            _ if opcode & 0xFFF0 == 0x00C0 => format!("SCD {}", n),
+            _ if opcode & 0xFFF0 == 0x00D0 => format!("SCU {}", n),
+            _ => format!("SYS {:#05X}", nnn),
+        },
+
+        0x1000 => format!("JP {:#05X}", nnn),
+        0x2000 => format!("CALL {:#05X}", nnn),
+        0x3000 => format!("SE V{:X}, {:#04X}", x, nn),
+        0x4000 => format!("SNE V{:X}, {:#04X}", x, nn),
+
+        0x5000 => match n {
+            0x0 => format!("SE V{:X}, V{:X}", x, y),
+            0x2 => format!("SAVE V{:X} - V{:X}", x, y),
+            0x3 => format!("LOAD V{:X} - V{:X}", x, y),
+            _ => unknown(opcode),
+        },
+
+        0x6000 => format!("LD V{:X}, {:#04X}", x, nn),
+        0x7000 => format!("ADD V{:X}, {:#04X}", x, nn),
+
+        0x8000 => {
+            let mnemonic = match n {
+                0x0 => "LD",
+                0x1 => "OR",
+                0x2 => "AND",
+                0x3 => "XOR",
+                0x4 => "ADD",
+                0x5 => "SUB",
+                0x6 => "SHR",
+                0x7 => "SUBN",
+                0xE => "SHL",
+                _ => return unknown(opcode),
+            };
+
+            format!("{} V{:X}, V{:X}", mnemonic, x, y)
+        }
+
+        0x9000 if n == 0 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA000 => format!("LD I, {:#05X}", nnn),
+        0xB000 => format!("JP V0, {:#05X}", nnn),
+        0xC000 => format!("RND V{:X}, {:#04X}", x, nn),
+        0xD000 => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+
+        0xE000 => match nn {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => unknown(opcode),
+        },
+
+        0xF000 => match nn {
+            0x00 if x == 0 => "LD I, long".to_string(),
+            0x01 => format!("PLANE {}", x),
+            0x02 if x == 0 => "AUDIO".to_string(),
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x30 => format!("LD HF, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x3A => format!("PITCH V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            0x75 => format!("LD R, V{:X}", x),
+            0x85 => format!("LD V{:X}, R", x),
+            _ => unknown(opcode),
+        },
+
+        _ => unknown(opcode),
+    }
+}
+
+fn unknown(opcode: u16) -> String {
+    format!("DW {:#06X}", opcode)
+}
@@ -9,6 +9,10 @@ pub struct Display {
     pub active_planes: u8,
 
     pub resolution: Resolution,
+
+    /// Whether the most recent change to the display erased pixels, by a sprite collision or clearing
+    /// the display. Programs usually redraw right after, so the display is incomplete until then
+    pub erased: bool,
 }
 
 impl Display {
@@ -52,6 +56,7 @@ impl Default for Display {
             planes: [Plane::default(), Plane::default()],
             active_planes: 0b01,
             resolution: Resolution::default(),
+            erased: false,
         }
     }
 }
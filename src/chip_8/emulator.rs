@@ -1,18 +1,32 @@
-use crate::chip_8::config::Config;
+use crate::chip_8::archive;
+use crate::chip_8::cartridge::{Cartridge, OctoOptions};
+use crate::chip_8::config::{Config, EmulationOverrides, EmulationSettings, Platform, Timing};
+use crate::chip_8::coverage::Coverage;
+use crate::chip_8::debugger::Debugger;
 use crate::chip_8::display::Display;
+use crate::chip_8::error::EmulationError;
 use crate::chip_8::instructions;
 use crate::chip_8::keypad::Keypad;
 use crate::chip_8::memory::Memory;
+use crate::chip_8::movie::MovieSession;
+use crate::chip_8::profiler::Profiler;
+use crate::chip_8::rom_database::{self, RomInfo};
 use crate::chip_8::sound::Beeper;
 use crate::emulator_app::Event;
 use anyhow::{anyhow, Result};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::fs::File;
 use std::io::Read;
-use std::path::PathBuf;
+use std::mem;
+use std::path::{Path, PathBuf};
 
 // Instructions start at 0x200, since 0x000 - 0x1FF are reserved for interpreter
 pub const INSTRUCTIONS_START: usize = 0x200;
 
+// File extensions of ROMs. Octo cartridges are GIF images
+pub const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "sc8", "xo8", "gif"];
+
 #[derive(Clone)]
 pub struct Emulator {
     pub config: Config,
@@ -27,6 +41,12 @@ pub struct Emulator {
     pub memory: Memory,
     pub rom_loaded: bool,
 
+    // Hex encoded SHA-1 of the last loaded ROM
+    pub rom_hash: Option<String>,
+
+    // Entry of the last loaded ROM in the ROM database
+    pub rom_info: Option<RomInfo>,
+
     // Program Counter
     // Used to store location of the next instruction
     pub pc: usize,
@@ -54,6 +74,30 @@ pub struct Emulator {
     // Sound Timer
     // Functions like the Delay Timer, however also gives of a beep sounds when not 0
     pub sound_timer: u8,
+
+    // Instructions per second which did not add up to a whole instruction yet, in 1/60 instructions
+    // Carried over to the next frame, so that no instructions get lost when they don't divide evenly
+    pub instruction_remainder: u32,
+
+    // Amount of frames emulated since the last reset
+    pub frames: u64,
+
+    // Random number generator used by CXNN
+    // Seeded, so that sessions can be replayed exactly
+    pub rng: StdRng,
+    pub seed: u32,
+
+    // Movie which is being recorded or played back
+    pub movie: Option<MovieSession>,
+
+    // Breakpoints and watchpoints
+    pub debugger: Debugger,
+
+    // Counts the executed instructions while profiling
+    pub profiler: Option<Profiler>,
+
+    // Records which bytes of memory were executed, read and written
+    pub coverage: Option<Coverage>,
 }
 
 impl Default for Emulator {
@@ -67,6 +111,8 @@ impl Emulator {
         let mut memory = Memory::default();
         memory.load_fonts();
 
+        let seed = rand::random();
+
         Self {
             memory,
             config,
@@ -80,7 +126,17 @@ impl Emulator {
             f_regs: [0; 16],
             delay_timer: 0,
             sound_timer: 0,
-            rom_loaded: false
+            instruction_remainder: 0,
+            frames: 0,
+            rng: StdRng::seed_from_u64(seed as u64),
+            seed,
+            movie: None,
+            debugger: Debugger::default(),
+            profiler: None,
+            coverage: None,
+            rom_loaded: false,
+            rom_hash: None,
+            rom_info: None,
         }
     }
 
@@ -93,40 +149,45 @@ impl Emulator {
     }
 
     pub fn load_rom(&mut self) -> Result<()> {
-        if let Some(filepath) = &self.config.filepath {
-            let file = File::open(filepath);
-
-            match file {
-                Ok(mut file) => {
-                    let mut data = vec![];
-                    let result = file.read_to_end(&mut data);
-
-                    if let Err(error) = result {
-                        return Err(anyhow!(error).context(format!(
-                            "Error reading file at {}\nPlease ensure it is a valid file.",
-                            filepath.display()
-                        )));
-                    }
-
-                    if data.len() > self.memory.size - INSTRUCTIONS_START {
-                        return Err(anyhow!(
-                            "File with size of {} bytes exceeds maximum data size of {} bytes.",
-                            data.len(),
-                            self.memory.size - INSTRUCTIONS_START
-                        ));
-                    }
-
-                    self.memory.load_rom(&data);
-                    self.rom_loaded = true;
-                }
-
-                Err(error) => {
-                    return Err(anyhow!(error).context(format!(
-                        "Error opening file at {}\nPlease ensure the path points to a valid file",
+        if let Some(filepath) = &self.config.filepath.clone() {
+            let mut data = Self::read_rom(filepath)?;
+            let mut octo_options = None;
+
+            if Cartridge::is_cartridge(&data) {
+                let cartridge = Cartridge::load(&data).map_err(|error| {
+                    error.context(format!(
+                        "Error loading Octo cartridge at {}",
                         filepath.display()
-                    )))
+                    ))
+                })?;
+
+                data = cartridge.rom;
+                octo_options = Some(cartridge.options);
+            }
+
+            let hash = rom_database::hash(&data);
+
+            // Only configure the ROM the first time it is loaded, so that changes to the
+            // settings are kept when it is reloaded
+            if self.rom_hash.as_ref() != Some(&hash) {
+                self.rom_info = rom_database::lookup(&hash);
+                self.rom_hash = Some(hash);
+                self.configure_rom(octo_options.as_ref());
+            }
+
+            if data.len() > self.memory.size - INSTRUCTIONS_START {
+                return Err(EmulationError::RomTooLarge {
+                    size: data.len(),
+                    max_size: self.memory.size - INSTRUCTIONS_START,
                 }
+                .into());
             }
+
+            self.memory.load_rom(&data);
+            self.rom_loaded = true;
+
+            let timestamp = chrono::Local::now().timestamp();
+            self.config.library.mark_played(filepath, timestamp);
         } else {
             return Err(anyhow!("Trying to load ROM without any selected"));
         }
@@ -134,16 +195,256 @@ impl Emulator {
         Ok(())
     }
 
+    /// Read the data of a ROM file, or of a ROM inside an archive
+    fn read_rom(filepath: &Path) -> Result<Vec<u8>> {
+        if archive::is_archive(filepath) {
+            return archive::read_rom(filepath, None);
+        }
+
+        if let Some((archive, entry)) = archive::split_path(filepath) {
+            return archive::read_rom(archive, Some(&entry));
+        }
+
+        let mut data = vec![];
+
+        File::open(filepath)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|source| EmulationError::Io {
+                path: filepath.to_path_buf(),
+                source,
+            })?;
+
+        Ok(data)
+    }
+
+    /// Apply the ROM database, the options of an Octo cartridge and the emulation settings saved
+    /// for the loaded ROM.
+    /// The global emulation settings are kept aside meanwhile, and restored for the next ROM
+    fn configure_rom(&mut self, octo_options: Option<&OctoOptions>) {
+        if let Some(global) = self.config.global_emulation.take() {
+            self.apply_emulation_settings(global);
+        }
+
+        let global = self.emulation_settings();
+
+        if self.config.use_rom_database {
+            self.apply_rom_info();
+        }
+
+        if let Some(octo_options) = octo_options {
+            octo_options.apply(self);
+        }
+
+        if let Some(settings) = self
+            .config
+            .rom_name()
+            .and_then(|rom_name| self.config.rom_overrides.get(&rom_name))
+        {
+            self.apply_emulation_settings(*settings);
+        }
+
+        self.apply_emulation_overrides();
+
+        if self.emulation_settings() != global {
+            self.config.global_emulation = Some(global);
+        }
+    }
+
+    /// Force emulation settings for the rest of the session, on top of the settings of the loaded
+    /// ROM and of every ROM loaded later
+    pub fn set_emulation_overrides(&mut self, overrides: EmulationOverrides) {
+        // Keep the global settings aside, so the overrides aren't saved
+        let global = self.emulation_settings();
+        self.config.global_emulation.get_or_insert(global);
+
+        self.config.emulation_overrides = overrides;
+        self.apply_emulation_overrides();
+    }
+
+    fn apply_emulation_overrides(&mut self) {
+        let overrides = self.config.emulation_overrides;
+
+        if let Some(platform) = overrides.platform {
+            self.apply_platform(platform);
+        }
+
+        let quirks = &mut self.config.quirks;
+
+        for (option, quirk) in [
+            (overrides.vf_reset, &mut quirks.vf_reset),
+            (overrides.increment_i_reg, &mut quirks.increment_i_reg),
+            (overrides.vx_offset_jump, &mut quirks.vx_offset_jump),
+            (overrides.shift_vx_directly, &mut quirks.shift_vx_directly),
+            (overrides.wrap_sprites, &mut quirks.wrap_sprites),
+        ] {
+            if let Some(value) = option {
+                *quirk = value;
+            }
+        }
+
+        if let Some(ipf) = overrides.instructions_per_frame {
+            self.config.timing = Timing::InstructionsPerFrame;
+            self.config.instructions_per_frame = ipf;
+        }
+    }
+
+    /// Configure the emulator for the loaded ROM, as suggested by the ROM database
+    fn apply_rom_info(&mut self) {
+        let Some(info) = self.rom_info.clone() else {
+            self.keypad.rom_keys.clear();
+            return;
+        };
+
+        if let Some(platform) = info.platform {
+            self.apply_platform(platform);
+        }
+
+        if let Some(tickrate) = info.tickrate {
+            self.config.timing = Timing::InstructionsPerFrame;
+            self.config.instructions_per_frame = tickrate;
+        }
+
+        if let (Some(palette), Some(rom_name)) = (info.palette, self.config.rom_name()) {
+            // A palette chosen by the user for this ROM takes precedence
+            self.config.rom_palettes.entry(rom_name).or_insert(palette);
+        }
+
+        self.keypad.rom_keys = info.keys;
+    }
+
+    pub fn emulation_settings(&self) -> EmulationSettings {
+        EmulationSettings {
+            timing: self.config.timing,
+            instructions_per_frame: self.config.instructions_per_frame,
+            instructions_per_second: self.config.instructions_per_second,
+            quirks: self.config.quirks,
+            memory_size: self.memory.size,
+        }
+    }
+
+    pub fn apply_emulation_settings(&mut self, settings: EmulationSettings) {
+        self.config.timing = settings.timing;
+        self.config.instructions_per_frame = settings.instructions_per_frame;
+        self.config.instructions_per_second = settings.instructions_per_second;
+        self.config.quirks = settings.quirks;
+        self.memory.resize(settings.memory_size);
+    }
+
+    /// Use the quirks, memory size and speed of a platform
+    pub fn apply_platform(&mut self, platform: Platform) {
+        self.config.quirks = platform.quirks();
+        self.config.timing = Timing::InstructionsPerFrame;
+        self.config.instructions_per_frame = platform.instructions_per_frame();
+        self.memory.resize(platform.memory_size());
+    }
+
     pub fn reset(&mut self) {
         self.rom_loaded = false;
 
-        self.beeper.stop();
+        self.beeper.pause();
         let keypad = self.keypad.clone();
+        let debugger = self.debugger.clone();
+        let memory_size = self.memory.size;
+        let rom_hash = self.rom_hash.take();
+        let rom_info = self.rom_info.take();
+        let beeper = mem::take(&mut self.beeper);
 
         *self = Emulator::new(self.config.clone());
 
+        // Keep playing through the same audio output
+        self.beeper = beeper;
+
         // Set keypad to previous, due to keybindings
         self.keypad = keypad;
+        self.debugger = debugger;
+
+        // Keep the ROM identification, so the database configuration isn't applied again on reload
+        self.rom_hash = rom_hash;
+        self.rom_info = rom_info;
+
+        // Keep the selected memory size, so movies and reloaded ROMs run with the same memory
+        self.memory.resize(memory_size);
+    }
+
+    /// Emulate a single frame, which is 1/60 of a second in emulated time.
+    /// Ticks the timers once and executes all instructions due in this frame
+    pub fn run_frame(&mut self) -> Result<(), Event> {
+        self.start_frame()?;
+
+        for _ in 0..self.instructions_due() {
+            self.execute_instruction()?;
+        }
+
+        self.end_frame()
+    }
+
+    /// Start emulating a frame, which plays back the inputs of a movie and ticks the timers.
+    /// Followed by the instructions due, and `end_frame`
+    pub fn start_frame(&mut self) -> Result<(), Event> {
+        if let Some(mut movie) = self.movie.take() {
+            let result = movie.start_frame(self);
+            self.movie = Some(movie);
+            result?;
+        }
+
+        self.frames += 1;
+        self.tick_timers();
+
+        // Cheats aren't part of movies, so they would make replays desync
+        if self.movie.is_none() {
+            self.apply_cheats();
+        }
+
+        Ok(())
+    }
+
+    /// Write the values of the enabled cheats of the ROM into memory
+    fn apply_cheats(&mut self) {
+        let Some(cheats) = self
+            .config
+            .rom_name()
+            .and_then(|rom_name| self.config.cheats.get(&rom_name))
+        else {
+            return;
+        };
+
+        for cheat in cheats.iter().filter(|cheat| cheat.enabled) {
+            cheat.apply(&mut self.memory);
+        }
+    }
+
+    pub fn end_frame(&mut self) -> Result<(), Event> {
+        // The beeper sounds for every frame ending with the sound timer running, so beeps last
+        // exactly as many frames as the timer was set to
+        self.beeper.push_frame(self.sound_timer > 0);
+        self.keypad.end_frame();
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end_frame();
+        }
+
+        if let Some(mut movie) = self.movie.take() {
+            let result = movie.end_frame(self);
+            self.movie = Some(movie);
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// How many instructions should be executed in the current frame
+    pub fn instructions_due(&mut self) -> u32 {
+        match self.config.timing {
+            Timing::InstructionsPerFrame => self.config.instructions_per_frame,
+            Timing::InstructionsPerSecond => {
+                self.instruction_remainder += self.config.instructions_per_second;
+
+                let instructions = self.instruction_remainder / 60;
+                self.instruction_remainder %= 60;
+
+                instructions
+            }
+        }
     }
 
     pub fn execute_instruction(&mut self) -> Result<(), Event> {
@@ -153,16 +454,63 @@ impl Emulator {
             return Err(Event::Exit);
         }
 
+        if self.debugger.should_check(self.pc) {
+            // Take the debugger out, so breakpoint conditions can look at the emulator
+            let mut debugger = mem::take(&mut self.debugger);
+            let hit = debugger.check_breakpoint(self);
+            self.debugger = debugger;
+
+            if hit {
+                self.beeper.pause();
+                return Err(Event::Break);
+            }
+        }
+
+        let instruction_pc = self.pc;
+
         // Fetch opcode
-        let opcode = (self.memory[self.pc] as u16) << 8 | (self.memory[self.pc + 1] as u16);
+        let opcode =
+            (self.memory.data[self.pc] as u16) << 8 | (self.memory.data[self.pc + 1] as u16);
         self.pc += 2;
 
+        self.memory.track_accesses =
+            !self.debugger.watchpoints.is_empty() || self.coverage.is_some();
+
         // Execute instruction
-        if let Err(error) = instructions::execute_instruction(self, opcode) {
-            Err(Event::ReportError(error))
-        } else {
-            Ok(())
+        let result = instructions::execute_instruction(self, opcode);
+        let accesses = self.memory.take_accesses();
+
+        if let Err(error) = result {
+            // Point at the faulting instruction, for the debugger
+            self.pc = instruction_pc;
+            return Err(Event::ReportError(error.into()));
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            // Reading a running delay timer, waiting for a key press and jumping to itself
+            let waiting =
+                (opcode & 0xF0FF == 0xF007 && self.delay_timer != 0) || self.pc == instruction_pc;
+            profiler.record_instruction(instruction_pc, opcode, waiting);
+        }
+
+        if let Some(coverage) = &mut self.coverage {
+            let length = if opcode == 0xF000 { 4 } else { 2 };
+            coverage.record_execution(instruction_pc, length);
+            coverage.record_accesses(&accesses);
+        }
+
+        if self.debugger.check_watchpoints(instruction_pc, &accesses) {
+            self.beeper.pause();
+            return Err(Event::Break);
         }
+
+        Ok(())
+    }
+
+    /// Execute a single instruction from the debugger, even if there is a breakpoint at it
+    pub fn step(&mut self) -> Result<(), Event> {
+        self.debugger.resume();
+        self.execute_instruction()
     }
 
     pub fn tick_timers(&mut self) {
@@ -172,17 +520,49 @@ impl Emulator {
 
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
+        }
+    }
 
-            if self.sound_timer == 0 {
-                self.beeper.pause();
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed as u64);
+    }
+
+    /// Hash of the emulated machine state, used to check if a replayed movie is still in sync.
+    /// Uses FNV-1a, so hashes stay the same across platforms and versions
+    pub fn state_hash(&self) -> u32 {
+        let mut hash: u32 = 0x811C9DC5;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes {
+                hash = (hash ^ *byte as u32).wrapping_mul(0x01000193);
+            }
+        };
+
+        write(&(self.pc as u32).to_le_bytes());
+        write(&(self.i_reg as u32).to_le_bytes());
+        write(&self.v_regs);
+        write(&[self.delay_timer, self.sound_timer]);
+
+        for address in &self.stack {
+            write(&(*address as u32).to_le_bytes());
+        }
+
+        write(&self.memory.data);
+
+        for plane in &self.display.planes {
+            for row in &plane.pixels {
+                write(&row.iter().map(|pixel| *pixel as u8).collect::<Vec<u8>>());
             }
         }
+
+        hash
     }
 
     /// Skip the next instruction
     /// If the next instruction is F000, this will skip 4 bytes instead of 2
     pub fn skip_instruction(&mut self) {
-        let opcode = (self.memory[self.pc] as u16) << 8 | (self.memory[self.pc + 1] as u16);
+        let opcode =
+            (self.memory.data[self.pc] as u16) << 8 | (self.memory.data[self.pc + 1] as u16);
         if opcode == 0xF000 {
             self.pc += 4;
         } else {
@@ -0,0 +1,236 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, EmulationError>;
+
+/// Errors of the emulated machine. `pc` is the address of the faulting instruction, so frontends
+/// can point at it
+#[derive(Debug)]
+pub enum EmulationError {
+    UnknownOpcode {
+        pc: usize,
+        opcode: u16,
+    },
+
+    AddressOutOfBounds {
+        pc: usize,
+        opcode: u16,
+        address: usize,
+    },
+
+    /// Returning from a subroutine while none was called
+    StackUnderflow {
+        pc: usize,
+        opcode: u16,
+    },
+
+    /// Calling more nested subroutines than `MAX_STACK_DEPTH`
+    StackOverflow {
+        pc: usize,
+        opcode: u16,
+    },
+
+    RomTooLarge {
+        size: usize,
+        max_size: usize,
+    },
+
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// How many subroutines can be nested. Deeper than any original interpreter allows, so only
+/// runaway recursion fails
+pub const MAX_STACK_DEPTH: usize = 256;
+
+impl EmulationError {
+    /// Address of the instruction which caused the error
+    pub fn pc(&self) -> Option<usize> {
+        match self {
+            EmulationError::UnknownOpcode { pc, .. }
+            | EmulationError::AddressOutOfBounds { pc, .. }
+            | EmulationError::StackUnderflow { pc, .. }
+            | EmulationError::StackOverflow { pc, .. } => Some(*pc),
+            EmulationError::RomTooLarge { .. } | EmulationError::Io { .. } => None,
+        }
+    }
+
+    pub fn opcode(&self) -> Option<u16> {
+        match self {
+            EmulationError::UnknownOpcode { opcode, .. }
+            | EmulationError::AddressOutOfBounds { opcode, .. }
+            | EmulationError::StackUnderflow { opcode, .. }
+            | EmulationError::StackOverflow { opcode, .. } => Some(*opcode),
+            EmulationError::RomTooLarge { .. } | EmulationError::Io { .. } => None,
+        }
+    }
+}
+
+impl Display for EmulationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmulationError::UnknownOpcode { pc, opcode } => write!(
+                f,
+                "Unknown instruction: {:#06X}\nInstruction is located at memory location {:#05X}",
+                opcode, pc
+            ),
+            EmulationError::AddressOutOfBounds {
+                pc,
+                opcode,
+                address,
+            } => write!(
+                f,
+                "Memory address {:#05X} is out of bounds\nInstruction {:#06X} is located at \
+                 memory location {:#05X}",
+                address, opcode, pc
+            ),
+            EmulationError::StackUnderflow { pc, opcode } => write!(
+                f,
+                "No subroutine to return from\nInstruction {:#06X} is located at memory location \
+                 {:#05X}",
+                opcode, pc
+            ),
+            EmulationError::StackOverflow { pc, opcode } => write!(
+                f,
+                "More than {} nested subroutines\nInstruction {:#06X} is located at memory \
+                 location {:#05X}",
+                MAX_STACK_DEPTH, opcode, pc
+            ),
+            EmulationError::RomTooLarge { size, max_size } => write!(
+                f,
+                "File with size of {} bytes exceeds maximum data size of {} bytes.",
+                size, max_size
+            ),
+            EmulationError::Io { path, .. } => write!(
+                f,
+                "Error reading file at {}\nPlease ensure the path points to a valid file",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EmulationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmulationError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip_8::emulator::{Emulator, INSTRUCTIONS_START};
+    use crate::emulator_app::Event;
+    use std::fs;
+
+    /// Run the program until it fails, returning the emulator and the error
+    fn run_until_error(program: &[u8]) -> (Emulator, EmulationError) {
+        let mut emulator = Emulator::default();
+        emulator.memory.load_rom(&program.to_vec());
+
+        for _ in 0..1000 {
+            match emulator.execute_instruction() {
+                Ok(()) => {}
+                Err(Event::ReportError(error)) => {
+                    let error = error
+                        .downcast::<EmulationError>()
+                        .expect("not an emulation error");
+                    return (emulator, error);
+                }
+                Err(_) => panic!("the program stopped without an error"),
+            }
+        }
+
+        panic!("the program didn't fail");
+    }
+
+    /// Check that the error and the emulator point at the instruction at `offset` in the program
+    fn assert_faults_at(program: &[u8], offset: usize, opcode: u16) -> EmulationError {
+        let (emulator, error) = run_until_error(program);
+        let pc = INSTRUCTIONS_START + offset;
+
+        assert_eq!(error.pc(), Some(pc));
+        assert_eq!(error.opcode(), Some(opcode));
+        assert_eq!(emulator.pc, pc);
+
+        error
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let error = assert_faults_at(&[0x60, 0x01, 0xE0, 0xFF], 2, 0xE0FF);
+
+        assert!(matches!(error, EmulationError::UnknownOpcode { .. }));
+    }
+
+    #[test]
+    fn store_registers_past_memory() {
+        // I = 0xFFF, then store V0 and V1
+        let error = assert_faults_at(&[0xAF, 0xFF, 0xF1, 0x55], 2, 0xF155);
+
+        assert!(matches!(
+            error,
+            EmulationError::AddressOutOfBounds {
+                address: 0x1000,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn draw_sprite_past_memory() {
+        // I = 0xFFF, then draw a sprite of 2 rows
+        let error = assert_faults_at(&[0xAF, 0xFF, 0xD0, 0x02], 2, 0xD002);
+
+        assert!(matches!(
+            error,
+            EmulationError::AddressOutOfBounds {
+                address: 0x1000,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn return_without_call() {
+        let error = assert_faults_at(&[0x00, 0xEE], 0, 0x00EE);
+
+        assert!(matches!(error, EmulationError::StackUnderflow { .. }));
+    }
+
+    #[test]
+    fn endless_recursion() {
+        // Call itself until the stack is full
+        let error = assert_faults_at(&[0x22, 0x00], 0, 0x2200);
+
+        assert!(matches!(error, EmulationError::StackOverflow { .. }));
+    }
+
+    #[test]
+    fn rom_too_large() {
+        let filepath = std::env::temp_dir().join("chip_8_rom_too_large_test.ch8");
+        fs::write(&filepath, vec![0; 4096 - INSTRUCTIONS_START + 1]).unwrap();
+
+        let mut emulator = Emulator::default();
+        emulator.select_rom(filepath.clone());
+        let error = emulator.load_rom().unwrap_err();
+        fs::remove_file(&filepath).unwrap();
+
+        let error = error.downcast::<EmulationError>().unwrap();
+
+        assert!(matches!(
+            error,
+            EmulationError::RomTooLarge {
+                size: 3585,
+                max_size: 3584,
+            }
+        ));
+        assert_eq!(error.pc(), None);
+        assert_eq!(error.opcode(), None);
+    }
+}
@@ -0,0 +1,509 @@
+use crate::chip_8::emulator::Emulator;
+use anyhow::{anyhow, Result};
+use std::fmt::{Display, Formatter};
+
+/// An expression over the machine state, like `v3 == 0x10 && i > 0x400` or `mem[0x300] != 0`.
+/// Values are integers, comparisons and logical operators give 1 or 0, and any value other than 0
+/// is true. Operators and their precedence follow C, division by zero gives 0.
+///
+/// Available values are the registers `v0` - `vf`, `i`, `pc`, the stack pointer `sp`, the timers
+/// `dt` and `st`, the emulated `frame` and bytes of memory as `mem[address]`
+#[derive(Clone)]
+pub struct Expression {
+    source: String,
+    node: Node,
+}
+
+/// A message with expressions in braces, which are replaced by their values, like
+/// `score: {mem[0x300]}`. Values are shown in hex with a `:x` suffix, like `{i:x}`
+#[derive(Clone)]
+pub struct Template {
+    source: String,
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Clone)]
+enum TemplatePart {
+    Text(String),
+    Value { expression: Expression, hex: bool },
+}
+
+#[derive(Clone)]
+enum Node {
+    Number(i64),
+    Value(Value),
+    Memory(Box<Node>),
+    Unary(UnaryOperator, Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, Copy)]
+enum Value {
+    Register(usize),
+    I,
+    Pc,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+    Frame,
+}
+
+#[derive(Clone, Copy)]
+enum UnaryOperator {
+    Not,
+    Negate,
+    Complement,
+}
+
+#[derive(Clone, Copy)]
+enum BinaryOperator {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+/// Binary operators by precedence, from lowest to highest
+const PRECEDENCE: [&[(&str, BinaryOperator)]; 10] = [
+    &[("||", BinaryOperator::Or)],
+    &[("&&", BinaryOperator::And)],
+    &[("|", BinaryOperator::BitOr)],
+    &[("^", BinaryOperator::BitXor)],
+    &[("&", BinaryOperator::BitAnd)],
+    &[
+        ("==", BinaryOperator::Equal),
+        ("!=", BinaryOperator::NotEqual),
+    ],
+    &[
+        ("<=", BinaryOperator::LessEqual),
+        (">=", BinaryOperator::GreaterEqual),
+        ("<", BinaryOperator::Less),
+        (">", BinaryOperator::Greater),
+    ],
+    &[
+        ("<<", BinaryOperator::ShiftLeft),
+        (">>", BinaryOperator::ShiftRight),
+    ],
+    &[("+", BinaryOperator::Add), ("-", BinaryOperator::Subtract)],
+    &[
+        ("*", BinaryOperator::Multiply),
+        ("/", BinaryOperator::Divide),
+        ("%", BinaryOperator::Remainder),
+    ],
+];
+
+/// Symbols of the tokenizer, longer symbols first so they are matched before their prefixes
+const SYMBOLS: [&str; 24] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<", ">", "+", "-", "*", "/",
+    "%", "!", "~", "(", ")", "[", "]",
+];
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+
+        let node = parser.parse_binary(0)?;
+
+        if let Some(token) = parser.peek() {
+            return Err(anyhow!("Unexpected '{}' in expression '{}'", token, source));
+        }
+
+        Ok(Self {
+            source: source.trim().to_string(),
+            node,
+        })
+    }
+
+    pub fn evaluate(&self, emulator: &Emulator) -> i64 {
+        self.node.evaluate(emulator)
+    }
+
+    pub fn is_true(&self, emulator: &Emulator) -> bool {
+        self.evaluate(emulator) != 0
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| anyhow!("Missing '}}' in message '{}'", source))?;
+
+            if start > 0 {
+                parts.push(TemplatePart::Text(rest[..start].to_string()));
+            }
+
+            let inner = &rest[start + 1..end];
+            let (inner, hex) = match inner.strip_suffix(":x") {
+                Some(inner) => (inner, true),
+                None => (inner, false),
+            };
+
+            parts.push(TemplatePart::Value {
+                expression: Expression::parse(inner)?,
+                hex,
+            });
+
+            rest = &rest[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Text(rest.to_string()));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    pub fn format(&self, emulator: &Emulator) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Value { expression, hex } => {
+                    let value = expression.evaluate(emulator);
+
+                    if *hex {
+                        format!("{:#X}", value)
+                    } else {
+                        value.to_string()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Node {
+    fn evaluate(&self, emulator: &Emulator) -> i64 {
+        match self {
+            Node::Number(number) => *number,
+            Node::Value(value) => value.get(emulator),
+
+            Node::Memory(address) => {
+                usize::try_from(address.evaluate(emulator))
+                    .ok()
+                    .and_then(|address| emulator.memory.data.get(address))
+                    .map_or(0, |byte| *byte as i64)
+            }
+
+            Node::Unary(operator, operand) => {
+                let operand = operand.evaluate(emulator);
+
+                match operator {
+                    UnaryOperator::Not => (operand == 0) as i64,
+                    UnaryOperator::Negate => operand.wrapping_neg(),
+                    UnaryOperator::Complement => !operand,
+                }
+            }
+
+            Node::Binary(BinaryOperator::Or, left, right) => {
+                (left.evaluate(emulator) != 0 || right.evaluate(emulator) != 0) as i64
+            }
+
+            Node::Binary(BinaryOperator::And, left, right) => {
+                (left.evaluate(emulator) != 0 && right.evaluate(emulator) != 0) as i64
+            }
+
+            Node::Binary(operator, left, right) => {
+                operator.apply(left.evaluate(emulator), right.evaluate(emulator))
+            }
+        }
+    }
+}
+
+impl Value {
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+
+        if let Some(register) = name.strip_prefix('v') {
+            if register.len() == 1 {
+                return usize::from_str_radix(register, 16)
+                    .ok()
+                    .map(Value::Register);
+            }
+        }
+
+        Some(match name.as_str() {
+            "i" => Value::I,
+            "pc" => Value::Pc,
+            "sp" => Value::Sp,
+            "dt" => Value::DelayTimer,
+            "st" => Value::SoundTimer,
+            "frame" => Value::Frame,
+            _ => return None,
+        })
+    }
+
+    fn get(&self, emulator: &Emulator) -> i64 {
+        match self {
+            Value::Register(register) => emulator.v_regs[*register] as i64,
+            Value::I => emulator.i_reg as i64,
+            Value::Pc => emulator.pc as i64,
+            Value::Sp => emulator.stack.len() as i64,
+            Value::DelayTimer => emulator.delay_timer as i64,
+            Value::SoundTimer => emulator.sound_timer as i64,
+            Value::Frame => emulator.frames as i64,
+        }
+    }
+}
+
+impl BinaryOperator {
+    fn apply(&self, left: i64, right: i64) -> i64 {
+        match self {
+            BinaryOperator::Or => (left != 0 || right != 0) as i64,
+            BinaryOperator::And => (left != 0 && right != 0) as i64,
+            BinaryOperator::BitOr => left | right,
+            BinaryOperator::BitXor => left ^ right,
+            BinaryOperator::BitAnd => left & right,
+            BinaryOperator::Equal => (left == right) as i64,
+            BinaryOperator::NotEqual => (left != right) as i64,
+            BinaryOperator::Less => (left < right) as i64,
+            BinaryOperator::LessEqual => (left <= right) as i64,
+            BinaryOperator::Greater => (left > right) as i64,
+            BinaryOperator::GreaterEqual => (left >= right) as i64,
+            BinaryOperator::ShiftLeft => left.wrapping_shl(right as u32),
+            BinaryOperator::ShiftRight => left.wrapping_shr(right as u32),
+            BinaryOperator::Add => left.wrapping_add(right),
+            BinaryOperator::Subtract => left.wrapping_sub(right),
+            BinaryOperator::Multiply => left.wrapping_mul(right),
+            BinaryOperator::Divide => left.checked_div(right).unwrap_or(0),
+            BinaryOperator::Remainder => left.checked_rem(right).unwrap_or(0),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unexpected end of expression"))?;
+
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        let token = self.next()?;
+
+        if token != expected {
+            return Err(anyhow!("Expected '{}', found '{}'", expected, token));
+        }
+
+        Ok(())
+    }
+
+    /// Parse binary operators with at least the given precedence
+    fn parse_binary(&mut self, precedence: usize) -> Result<Node> {
+        if precedence == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut left = self.parse_binary(precedence + 1)?;
+
+        while let Some(operator) = self.peek().and_then(|token| {
+            PRECEDENCE[precedence]
+                .iter()
+                .find(|(symbol, _)| *symbol == token)
+                .map(|(_, operator)| *operator)
+        }) {
+            self.position += 1;
+
+            let right = self.parse_binary(precedence + 1)?;
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node> {
+        let operator = match self.peek() {
+            Some("!") => UnaryOperator::Not,
+            Some("-") => UnaryOperator::Negate,
+            Some("~") => UnaryOperator::Complement,
+            _ => return self.parse_primary(),
+        };
+
+        self.position += 1;
+
+        Ok(Node::Unary(operator, Box::new(self.parse_unary()?)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Node> {
+        let token = self.next()?;
+
+        if token == "(" {
+            let node = self.parse_binary(0)?;
+            self.expect(")")?;
+
+            return Ok(node);
+        }
+
+        if token.eq_ignore_ascii_case("mem") {
+            self.expect("[")?;
+            let address = self.parse_binary(0)?;
+            self.expect("]")?;
+
+            return Ok(Node::Memory(Box::new(address)));
+        }
+
+        if let Some(number) = parse_number(&token) {
+            return Ok(Node::Number(number));
+        }
+
+        Value::from_name(&token)
+            .map(Node::Value)
+            .ok_or_else(|| anyhow!("Unknown value '{}'", token))
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            tokens.push(symbol.to_string());
+            rest = &rest[symbol.len()..];
+        } else {
+            let length = rest
+                .find(|char: char| !char.is_ascii_alphanumeric() && char != '_')
+                .unwrap_or(rest.len());
+
+            if length == 0 {
+                return Err(anyhow!(
+                    "Unexpected character '{}' in expression '{}'",
+                    rest.chars().next().unwrap_or_default(),
+                    source
+                ));
+            }
+
+            tokens.push(rest[..length].to_string());
+            rest = &rest[length..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a decimal, `0x` hexadecimal or `0b` binary number
+fn parse_number(token: &str) -> Option<i64> {
+    let lowercase = token.to_lowercase();
+
+    if let Some(hex) = lowercase.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lowercase.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else {
+        lowercase.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(source: &str) -> i64 {
+        let mut emulator = Emulator::default();
+        emulator.v_regs[3] = 0x10;
+        emulator.i_reg = 0x400;
+        emulator.memory.data[0x300] = 7;
+
+        Expression::parse(source).unwrap().evaluate(&emulator)
+    }
+
+    #[test]
+    fn arithmetic_follows_c_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), 7);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9);
+        assert_eq!(evaluate("10 - 4 - 3"), 3);
+        assert_eq!(evaluate("1 << 2 + 1"), 8);
+        assert_eq!(evaluate("-2 * 3"), -6);
+        assert_eq!(evaluate("7 / 0"), 0);
+    }
+
+    #[test]
+    fn comparisons_bind_tighter_than_logic() {
+        assert_eq!(evaluate("v3 == 0x10 && i > 0x3FF"), 1);
+        assert_eq!(evaluate("1 | 2 == 2"), 1);
+        assert_eq!(evaluate("6 & 3 == 3"), 0);
+        assert_eq!(evaluate("0 || 1 && 0"), 0);
+        assert_eq!(evaluate("1 || 1 && 0"), 1);
+        assert_eq!(evaluate("!v3 || ~0 == -1"), 1);
+    }
+
+    #[test]
+    fn memory_is_indexed_by_expressions() {
+        assert_eq!(evaluate("mem[0x300]"), 7);
+        assert_eq!(evaluate("mem[0x2FF + 1] * 2"), 14);
+        assert_eq!(evaluate("mem[-1]"), 0);
+    }
+
+    #[test]
+    fn invalid_expressions_fail() {
+        for source in [
+            "", "1 +", "(1", "mem[1", "1 2", "v10", "foo", "1 $ 2", "0xZZ",
+        ] {
+            assert!(Expression::parse(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn templates_format_values() {
+        let template = Template::parse("i: {i:x}, v3: {v3}").unwrap();
+
+        assert_eq!(template.format(&Emulator::default()), "i: 0x0, v3: 0");
+        assert!(Template::parse("{v3").is_err());
+        assert!(Template::parse("{1 +}").is_err());
+    }
+}
@@ -6,8 +6,9 @@ mod op_e;
 mod op_f;
 
 use crate::chip_8::emulator::Emulator;
+use crate::chip_8::error::{EmulationError, Result, MAX_STACK_DEPTH};
 use crate::chip_8::instructions::op_f::op_f;
-use anyhow::{anyhow, Result};
+use rand::Rng;
 
 pub fn execute_instruction(emulator: &mut Emulator, opcode: u16) -> Result<()> {
     match opcode & 0xF000 {
@@ -20,6 +21,13 @@ pub fn execute_instruction(emulator: &mut Emulator, opcode: u16) -> Result<()> {
 
         0x2000 => {
             // 2NNN - Call subroutine at NNN
+            if emulator.stack.len() == MAX_STACK_DEPTH {
+                return Err(EmulationError::StackOverflow {
+                    pc: emulator.pc - 2,
+                    opcode,
+                });
+            }
+
             emulator.stack.push(emulator.pc);
             emulator.pc = (opcode & 0x0FFF) as usize;
         }
@@ -86,7 +94,7 @@ pub fn execute_instruction(emulator: &mut Emulator, opcode: u16) -> Result<()> {
         0xC000 => {
             // CXNN - Binary AND a random number with NN and set VX to the number
             let vx = ((opcode & 0x0F00) >> 8) as usize;
-            emulator.v_regs[vx] = rand::random::<u8>() & (opcode & 0x00FF) as u8;
+            emulator.v_regs[vx] = emulator.rng.random::<u8>() & (opcode & 0x00FF) as u8;
         }
 
         0xD000 => op_dxyn::op_dxyn(emulator, opcode)?,
@@ -102,16 +110,16 @@ pub fn execute_instruction(emulator: &mut Emulator, opcode: u16) -> Result<()> {
 }
 
 fn unknown_instruction_err(emulator: &Emulator, opcode: u16) -> Result<()> {
-    Err(anyhow!(
-        "Unknown instruction: {:#06X}\nInstruction is located at memory location {}",
+    Err(EmulationError::UnknownOpcode {
+        pc: emulator.pc - 2,
         opcode,
-        emulator.pc - 2
-    ))
+    })
 }
 
 fn memory_index_out_of_bounds_err(index: usize, emulator: &Emulator, opcode: u16) -> Result<()> {
-    Err(anyhow!(
-        "Memory Index {} is out of bounds\nInstruction {:#06X} is located at memory location {}",
-        index, opcode, emulator.pc - 2
-    ))
+    Err(EmulationError::AddressOutOfBounds {
+        pc: emulator.pc - 2,
+        opcode,
+        address: index,
+    })
 }
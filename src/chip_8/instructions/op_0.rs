@@ -1,7 +1,7 @@
 use crate::chip_8::display::Resolution;
 use crate::chip_8::emulator::Emulator;
+use crate::chip_8::error::{EmulationError, Result};
 use crate::chip_8::instructions::unknown_instruction_err;
-use anyhow::{anyhow, Result};
 
 /// Execute instructions which start with 0
 pub fn op_0(emulator: &mut Emulator, opcode: u16) -> Result<()> {
@@ -25,6 +25,7 @@ pub fn op_0(emulator: &mut Emulator, opcode: u16) -> Result<()> {
                 0x0000 => {
                     // 00E0 - Clear display
                     emulator.display.for_active_plane(|plane| plane.clear());
+                    emulator.display.erased = true;
                 }
 
                 0x000E => {
@@ -32,7 +33,10 @@ pub fn op_0(emulator: &mut Emulator, opcode: u16) -> Result<()> {
                     match emulator.stack.pop() {
                         Some(pc) => emulator.pc = pc,
                         None => {
-                            return Err(anyhow!("No subroutine to return from\nInstruction {:#06x} is located at memory location {}", opcode, emulator.pc - 2));
+                            return Err(EmulationError::StackUnderflow {
+                                pc: emulator.pc - 2,
+                                opcode,
+                            });
                         }
                     }
                 }
@@ -1,6 +1,6 @@
 use crate::chip_8::emulator::Emulator;
+use crate::chip_8::error::Result;
 use crate::chip_8::instructions::{memory_index_out_of_bounds_err, unknown_instruction_err};
-use anyhow::Result;
 
 /// Execute instructions which start with 5
 pub fn op_5(emulator: &mut Emulator, opcode: u16) -> Result<()> {
@@ -26,7 +26,7 @@ pub fn op_5(emulator: &mut Emulator, opcode: u16) -> Result<()> {
 
             for i in 0..=diff {
                 let reg = if vx < vy { vx + i } else { vx - i };
-                emulator.memory[emulator.i_reg + i] = emulator.v_regs[reg];
+                emulator.memory.write(emulator.i_reg + i, emulator.v_regs[reg]);
             }
         }
 
@@ -41,7 +41,7 @@ pub fn op_5(emulator: &mut Emulator, opcode: u16) -> Result<()> {
 
             for i in 0..=diff {
                 let reg = if vx < vy { vx + i } else { vx - i };
-                emulator.v_regs[reg] = emulator.memory[emulator.i_reg + i];
+                emulator.v_regs[reg] = emulator.memory.read(emulator.i_reg + i);
             }
         }
 
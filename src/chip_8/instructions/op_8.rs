@@ -1,6 +1,6 @@
 use crate::chip_8::emulator::Emulator;
+use crate::chip_8::error::Result;
 use crate::chip_8::instructions::unknown_instruction_err;
-use anyhow::Result;
 
 /// Execute instructions which start with 8
 pub fn op_8(emulator: &mut Emulator, opcode: u16) -> Result<()> {
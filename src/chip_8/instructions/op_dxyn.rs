@@ -1,6 +1,6 @@
 use crate::chip_8::emulator::Emulator;
+use crate::chip_8::error::Result;
 use crate::chip_8::instructions::memory_index_out_of_bounds_err;
-use anyhow::Result;
 
 /// Execute DXYN Instruction
 pub fn op_dxyn(emulator: &mut Emulator, opcode: u16) -> Result<()> {
@@ -31,14 +31,14 @@ pub fn op_dxyn(emulator: &mut Emulator, opcode: u16) -> Result<()> {
                     return memory_index_out_of_bounds_err(i + row * 2 + 1, emulator, opcode);
                 }
 
-                ((emulator.memory[i + row * 2] as u16) << 8)
-                    | (emulator.memory[i + row * 2 + 1] as u16)
+                ((emulator.memory.read(i + row * 2) as u16) << 8)
+                    | (emulator.memory.read(i + row * 2 + 1) as u16)
             } else {
                 if i + row >= emulator.memory.size {
                     return memory_index_out_of_bounds_err(i + row, emulator, opcode);
                 }
 
-                emulator.memory[i + row] as u16
+                emulator.memory.read(i + row) as u16
             };
 
             for index in 0..sprite_width {
@@ -72,5 +72,7 @@ pub fn op_dxyn(emulator: &mut Emulator, opcode: u16) -> Result<()> {
         i += if height == 0 { 32 } else { height };
     }
 
+    emulator.display.erased = emulator.v_regs[0xF] == 1;
+
     Ok(())
 }
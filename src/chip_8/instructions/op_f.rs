@@ -1,6 +1,6 @@
 use crate::chip_8::emulator::Emulator;
+use crate::chip_8::error::Result;
 use crate::chip_8::instructions::{memory_index_out_of_bounds_err, unknown_instruction_err};
-use anyhow::Result;
 
 /// Execute instructions which start with F
 pub fn op_f(emulator: &mut Emulator, opcode: u16) -> Result<()> {
@@ -50,15 +50,8 @@ pub fn op_f(emulator: &mut Emulator, opcode: u16) -> Result<()> {
 
         0x0018 => {
             // FX18 - Set sound timer to value of VX
-            let currently_playing = emulator.sound_timer > 0;
-
+            // The beeper follows the sound timer at the end of the frame
             emulator.sound_timer = emulator.v_regs[((opcode & 0x0F00) >> 8) as usize];
-
-            if currently_playing && emulator.sound_timer == 0 {
-                emulator.beeper.pause();
-            } else if !currently_playing && emulator.sound_timer > 0 {
-                emulator.beeper.play();
-            }
         }
 
         0x001E => {
@@ -98,21 +91,21 @@ pub fn op_f(emulator: &mut Emulator, opcode: u16) -> Result<()> {
                 memory_index_out_of_bounds_err(emulator.i_reg + 2, emulator, opcode)?;
             }
 
-            emulator.memory[emulator.i_reg] = x / 100;
-            emulator.memory[emulator.i_reg + 1] = (x % 100) / 10;
-            emulator.memory[emulator.i_reg + 2] = x % 10;
+            emulator.memory.write(emulator.i_reg, x / 100);
+            emulator.memory.write(emulator.i_reg + 1, (x % 100) / 10);
+            emulator.memory.write(emulator.i_reg + 2, x % 10);
         }
 
         0x0055 => {
             // FX55 - Store registers V0 to VX in memory starting at address I
             let vx = ((opcode & 0x0F00) >> 8) as usize;
 
-            if emulator.i_reg + vx > emulator.memory.size {
+            if emulator.i_reg + vx >= emulator.memory.size {
                 memory_index_out_of_bounds_err(emulator.i_reg + vx, emulator, opcode)?;
             }
 
             for reg in 0..=vx {
-                emulator.memory[emulator.i_reg + reg] = emulator.v_regs[reg];
+                emulator.memory.write(emulator.i_reg + reg, emulator.v_regs[reg]);
             }
 
             increment_i_quirk(emulator, vx);
@@ -122,12 +115,12 @@ pub fn op_f(emulator: &mut Emulator, opcode: u16) -> Result<()> {
             // FX65 - Read registers V0 to VX from memory starting at address I
             let vx = ((opcode & 0x0F00) >> 8) as usize;
 
-            if emulator.i_reg + vx > emulator.memory.size {
+            if emulator.i_reg + vx >= emulator.memory.size {
                 memory_index_out_of_bounds_err(emulator.i_reg + vx, emulator, opcode)?;
             }
 
             for reg in 0..=vx {
-                emulator.v_regs[reg] = emulator.memory[emulator.i_reg + reg];
+                emulator.v_regs[reg] = emulator.memory.read(emulator.i_reg + reg);
             }
 
             increment_i_quirk(emulator, vx);
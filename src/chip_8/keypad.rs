@@ -1,26 +1,73 @@
 use eframe::egui;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::array::from_fn;
 use std::cmp::PartialEq;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 pub const HEX_KEYS: [u8; 16] = [1, 2, 3, 0xC, 4, 5, 6, 0xD, 7, 8, 9, 0xE, 0xA, 0, 0xB, 0xF];
 
 #[derive(Clone)]
 pub struct Keypad {
     pub keys: [Key; 16],
+    pub hotkeys: Hotkeys,
+
+    /// Bitmask of the keys held down on the on-screen keypad.
+    /// The least significant bit corresponds to key 0
+    pub touched_keys: u16,
+
+    /// Touched keys of the previous update, used to detect released keys
+    previously_touched_keys: u16,
+
+    /// Bitmask of the keys released since the last emulated frame, kept until a frame sees them
+    pending_releases: u16,
+
+    /// Additional keyboard keys for the loaded ROM, suggested by the ROM database
+    pub rom_keys: Vec<(egui::Key, u8)>,
 }
 
 impl Keypad {
     pub fn update_keys(&mut self, input_state: &egui::InputState) {
-        for key in self.keys.iter_mut() {
-            key.state = if input_state.key_down(key.egui_key) {
+        for (index, key) in self.keys.iter_mut().enumerate() {
+            let touched = self.touched_keys & (1 << index) != 0;
+            let previously_touched = self.previously_touched_keys & (1 << index) != 0;
+
+            let rom_keys = self
+                .rom_keys
+                .iter()
+                .filter(|(_, hex_key)| *hex_key == key.hex_key)
+                .map(|(egui_key, _)| *egui_key);
+            let keyboard_keys: Vec<egui::Key> = rom_keys.chain([key.egui_key]).collect();
+            let key_down = keyboard_keys.iter().any(|key| input_state.key_down(*key));
+            let key_released = keyboard_keys
+                .iter()
+                .any(|key| input_state.key_released(*key));
+
+            if key_released || previously_touched {
+                self.pending_releases |= 1 << index;
+            }
+
+            key.state = if key_down || touched {
                 KeyState::Pressed
-            } else if input_state.key_released(key.egui_key) {
+            } else if self.pending_releases & (1 << index) != 0 {
                 KeyState::Released
             } else {
                 KeyState::Unpressed
             }
         }
+
+        self.previously_touched_keys = self.touched_keys;
+    }
+
+    /// Show released keys to the ROM for a single emulated frame, however many updates of the keys
+    /// happen per frame
+    pub fn end_frame(&mut self) {
+        self.pending_releases = 0;
+
+        for key in &mut self.keys {
+            if key.state == KeyState::Released {
+                key.state = KeyState::Unpressed;
+            }
+        }
     }
 
     pub fn is_key_pressed(&self, key: usize) -> bool {
@@ -41,6 +88,68 @@ impl Keypad {
         None
     }
 
+    /// The state of all keys as bitmasks of the pressed and released keys.
+    /// The least significant bit corresponds to key 0
+    pub fn state_masks(&self) -> (u16, u16) {
+        let mut pressed = 0;
+        let mut released = 0;
+
+        for (key, key_state) in self.keys.iter().enumerate() {
+            match key_state.state {
+                KeyState::Pressed => pressed |= 1 << key,
+                KeyState::Released => released |= 1 << key,
+                KeyState::Unpressed => {}
+            }
+        }
+
+        (pressed, released)
+    }
+
+    /// Set the state of all keys from bitmasks, as returned by `state_masks`
+    pub fn set_state_masks(&mut self, pressed: u16, released: u16) {
+        for (key, key_state) in self.keys.iter_mut().enumerate() {
+            key_state.state = if pressed & (1 << key) != 0 {
+                KeyState::Pressed
+            } else if released & (1 << key) != 0 {
+                KeyState::Released
+            } else {
+                KeyState::Unpressed
+            }
+        }
+    }
+
+    /// The keybindings of the keypad and the hotkeys, in a form which can be saved
+    pub fn keybindings(&self) -> Keybindings {
+        Keybindings {
+            keypad: self
+                .keys
+                .iter()
+                .map(|key| {
+                    (
+                        format!("{:X}", key.hex_key),
+                        key.egui_key.name().to_string(),
+                    )
+                })
+                .collect(),
+            hotkeys: self.hotkeys.clone(),
+        }
+    }
+
+    /// Use saved keybindings. Keys missing from them keep their current binding
+    pub fn set_keybindings(&mut self, keybindings: &Keybindings) {
+        for key in &mut self.keys {
+            if let Some(egui_key) = keybindings
+                .keypad
+                .get(&format!("{:X}", key.hex_key))
+                .and_then(|name| egui::Key::from_name(name))
+            {
+                key.egui_key = egui_key;
+            }
+        }
+
+        self.hotkeys = keybindings.hotkeys.clone();
+    }
+
     pub fn default_key_mappings() -> HashMap<u8, egui::Key> {
         HashMap::from([
             (1, egui::Key::Num1),
@@ -68,7 +177,82 @@ impl Default for Keypad {
         let mappings = Self::default_key_mappings();
         let keys: [Key; 16] = from_fn(|i| Key::from_hex(i as u8, &mappings));
 
-        Self { keys }
+        Self {
+            keys,
+            hotkeys: Hotkeys::default(),
+            touched_keys: 0,
+            previously_touched_keys: 0,
+            pending_releases: 0,
+            rom_keys: Vec::new(),
+        }
+    }
+}
+
+/// Keybindings of the keypad and hotkeys, with keys stored by their names
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keybindings {
+    /// Names of the keyboard keys, keyed by the Chip 8 key as a hex digit
+    pub keypad: BTreeMap<String, String>,
+
+    pub hotkeys: Hotkeys,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Keypad::default().keybindings()
+    }
+}
+
+/// Keybindings for emulator functions, which are not part of the Chip 8 Keypad
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hotkeys {
+    /// Emulate at the fast forward speed while held down
+    #[serde(with = "key_name")]
+    pub fast_forward: egui::Key,
+
+    #[serde(with = "key_name")]
+    pub toggle_fullscreen: egui::Key,
+
+    #[serde(with = "key_name")]
+    pub screenshot: egui::Key,
+
+    /// Start or stop recording
+    #[serde(with = "key_name")]
+    pub toggle_recording: egui::Key,
+
+    /// Pause the emulation and open the debugger
+    #[serde(with = "key_name")]
+    pub open_debugger: egui::Key,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            // Not a key egui uses to navigate, like Tab
+            fast_forward: egui::Key::Backtick,
+            toggle_fullscreen: egui::Key::F11,
+            screenshot: egui::Key::F12,
+            toggle_recording: egui::Key::F9,
+            open_debugger: egui::Key::F10,
+        }
+    }
+}
+
+/// Serialize keys by their names, like `Tab` or `F11`
+mod key_name {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(key: &egui::Key, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(key.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<egui::Key, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        egui::Key::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("Unknown key: {}", name)))
     }
 }
 
@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::ops::{Index, IndexMut};
 use crate::chip_8::emulator::INSTRUCTIONS_START;
 
@@ -5,6 +6,26 @@ use crate::chip_8::emulator::INSTRUCTIONS_START;
 pub struct Memory {
     pub data: Vec<u8>,
     pub size: usize,
+
+    /// Size of the loaded ROM in bytes, which starts at `INSTRUCTIONS_START`
+    pub rom_size: usize,
+
+    /// Record every access through `read` and `write`, so the debugger can observe them.
+    /// Indexing is not recorded
+    pub track_accesses: bool,
+    accesses: RefCell<Vec<MemoryAccess>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct MemoryAccess {
+    pub address: usize,
+    pub kind: AccessKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
 }
 
 impl Default for Memory {
@@ -12,6 +33,9 @@ impl Default for Memory {
         Self {
             data: vec![0; 4096],
             size: 4096,
+            rom_size: 0,
+            track_accesses: false,
+            accesses: RefCell::new(Vec::new()),
         }
     }
 }
@@ -33,6 +57,7 @@ impl IndexMut<usize> for Memory {
 impl Memory {
     pub fn load_rom(&mut self, data: &Vec<u8>) {
         self.data[INSTRUCTIONS_START..INSTRUCTIONS_START + data.len()].copy_from_slice(&data);
+        self.rom_size = data.len();
     }
 
     pub fn load_fonts(&mut self) {
@@ -47,6 +72,29 @@ impl Memory {
         self.data.resize(size, 0);
         self.size = size;
     }
+
+    /// Read a byte as the program, which the debugger observes
+    pub fn read(&self, address: usize) -> u8 {
+        self.record_access(address, AccessKind::Read);
+        self.data[address]
+    }
+
+    /// Write a byte as the program, which the debugger observes
+    pub fn write(&mut self, address: usize, value: u8) {
+        self.record_access(address, AccessKind::Write);
+        self.data[address] = value;
+    }
+
+    /// Get and clear the accesses recorded since the last call
+    pub fn take_accesses(&self) -> Vec<MemoryAccess> {
+        self.accesses.take()
+    }
+
+    fn record_access(&self, address: usize, kind: AccessKind) {
+        if self.track_accesses {
+            self.accesses.borrow_mut().push(MemoryAccess { address, kind });
+        }
+    }
 }
 
 pub const SMALL_FONT: [u8; 80] = [
@@ -1,7 +1,21 @@
+pub mod archive;
+pub mod audio;
+pub mod cartridge;
+pub mod cheats;
 pub mod config;
+pub mod coverage;
+pub mod debugger;
+pub mod disassembler;
 pub mod emulator;
+pub mod error;
+pub mod expression;
 pub mod instructions;
 pub mod keypad;
 pub mod display;
 pub mod sound;
 pub mod memory;
+pub mod movie;
+pub mod octo;
+pub mod palette;
+pub mod profiler;
+pub mod rom_database;
@@ -0,0 +1,248 @@
+use crate::chip_8::config::{Quirks, Timing};
+use crate::chip_8::emulator::Emulator;
+use crate::emulator_app::Event;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Keypad inputs of a session, together with everything else needed to replay it exactly
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Movie {
+    /// File name of the recorded ROM. Only informational, the ROM itself is checked by the first
+    /// sync check
+    pub rom_name: String,
+
+    /// Seed of the random number generator used by CXNN
+    pub seed: u32,
+
+    pub memory_size: usize,
+    pub timing: Timing,
+    pub instructions_per_frame: u32,
+    pub instructions_per_second: u32,
+    pub quirks: Quirks,
+
+    /// Amount of frames in the movie
+    pub frames: u64,
+
+    /// How many frames are between two sync checks
+    pub sync_interval: u64,
+
+    /// Changes of the keypad state. Frames without a change keep the state of the previous frame
+    pub inputs: Vec<InputChange>,
+
+    /// Hashes of the emulator state, which are compared while replaying to detect desyncs
+    pub sync_checks: Vec<SyncCheck>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputChange {
+    pub frame: u64,
+
+    /// Bitmask of the pressed keys. The least significant bit corresponds to key 0
+    pub pressed: u16,
+
+    /// Bitmask of the keys released in this frame
+    pub released: u16,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SyncCheck {
+    pub frame: u64,
+    pub hash: u32,
+}
+
+impl Movie {
+    const SYNC_INTERVAL: u64 = 60;
+
+    /// Create an empty movie with the configuration of the emulator
+    pub fn new(emulator: &Emulator) -> Self {
+        let config = &emulator.config;
+
+        Self {
+            rom_name: config.rom_name().unwrap_or_default(),
+            seed: emulator.seed,
+            memory_size: emulator.memory.size,
+            timing: config.timing,
+            instructions_per_frame: config.instructions_per_frame,
+            instructions_per_second: config.instructions_per_second,
+            quirks: config.quirks,
+            frames: 0,
+            sync_interval: Self::SYNC_INTERVAL,
+            inputs: Vec::new(),
+            sync_checks: Vec::new(),
+        }
+    }
+
+    pub fn load(filepath: &Path) -> Result<Self> {
+        let text = fs::read_to_string(filepath).map_err(|error| {
+            anyhow!(error).context(format!("Error reading movie at {}", filepath.display()))
+        })?;
+
+        toml::from_str(&text).map_err(|error| {
+            anyhow!(error).context(format!("Invalid movie at {}", filepath.display()))
+        })
+    }
+
+    pub fn save(&self, filepath: &Path) -> Result<()> {
+        fs::write(filepath, toml::to_string(self)?).map_err(|error| {
+            anyhow!(error).context(format!("Error saving movie at {}", filepath.display()))
+        })
+    }
+
+    /// Reset the emulator into the state the movie was recorded in and load the ROM
+    pub fn apply(&self, emulator: &mut Emulator) -> Result<()> {
+        emulator.reset();
+        emulator.memory.resize(self.memory_size);
+        emulator.load_rom()?;
+
+        // Set after loading, so the recorded configuration overrides the ROM database
+        let config = &mut emulator.config;
+        config.timing = self.timing;
+        config.instructions_per_frame = self.instructions_per_frame;
+        config.instructions_per_second = self.instructions_per_second;
+        config.quirks = self.quirks;
+
+        emulator.memory.resize(self.memory_size);
+        emulator.set_seed(self.seed);
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum MovieMode {
+    Recording,
+    Playing,
+}
+
+/// A movie which is currently being recorded or played back
+#[derive(Clone)]
+pub struct MovieSession {
+    pub movie: Movie,
+    pub mode: MovieMode,
+
+    /// Index of the next input change to replay
+    next_input: usize,
+
+    /// Index of the next sync check to compare while replaying
+    next_sync_check: usize,
+
+    /// The first frame at which the state didn't match the recording
+    pub desynced_at: Option<u64>,
+}
+
+impl MovieSession {
+    pub fn record(emulator: &Emulator) -> Self {
+        Self::new(Movie::new(emulator), MovieMode::Recording)
+    }
+
+    pub fn play(movie: Movie) -> Self {
+        Self::new(movie, MovieMode::Playing)
+    }
+
+    fn new(movie: Movie, mode: MovieMode) -> Self {
+        Self {
+            movie,
+            mode,
+            next_input: 0,
+            next_sync_check: 0,
+            desynced_at: None,
+        }
+    }
+
+    /// Record or replay the keypad state, before the next frame is emulated
+    pub fn start_frame(&mut self, emulator: &mut Emulator) -> Result<(), Event> {
+        let frame = emulator.frames;
+
+        if frame == 0 {
+            // The initial state also covers the ROM, so a wrong ROM is detected right away
+            self.sync(emulator)?;
+        }
+
+        match self.mode {
+            MovieMode::Recording => {
+                let (pressed, released) = emulator.keypad.state_masks();
+                let last = self.movie.inputs.last();
+
+                if last.map_or(pressed != 0 || released != 0, |last| {
+                    last.pressed != pressed || last.released != released
+                }) {
+                    self.movie.inputs.push(InputChange {
+                        frame,
+                        pressed,
+                        released,
+                    });
+                }
+            }
+
+            MovieMode::Playing => {
+                if frame >= self.movie.frames {
+                    return Err(Event::MovieFinished);
+                }
+
+                let inputs = &self.movie.inputs;
+
+                while self.next_input < inputs.len() && inputs[self.next_input].frame <= frame {
+                    self.next_input += 1;
+                }
+
+                let (pressed, released) = match self.next_input {
+                    0 => (0, 0),
+                    next => (inputs[next - 1].pressed, inputs[next - 1].released),
+                };
+
+                emulator.keypad.set_state_masks(pressed, released);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record or compare the state hash, after a frame was emulated
+    pub fn end_frame(&mut self, emulator: &Emulator) -> Result<(), Event> {
+        if self.mode == MovieMode::Recording {
+            self.movie.frames = emulator.frames;
+        }
+
+        if emulator.frames.is_multiple_of(self.movie.sync_interval) {
+            self.sync(emulator)?;
+        }
+
+        Ok(())
+    }
+
+    fn sync(&mut self, emulator: &Emulator) -> Result<(), Event> {
+        let frame = emulator.frames;
+        let hash = emulator.state_hash();
+
+        match self.mode {
+            MovieMode::Recording => self.movie.sync_checks.push(SyncCheck { frame, hash }),
+
+            MovieMode::Playing => {
+                let checks = &self.movie.sync_checks;
+
+                while self.next_sync_check < checks.len()
+                    && checks[self.next_sync_check].frame < frame
+                {
+                    self.next_sync_check += 1;
+                }
+
+                let desynced = checks
+                    .get(self.next_sync_check)
+                    .is_some_and(|check| check.frame == frame && check.hash != hash);
+
+                if desynced && self.desynced_at.is_none() {
+                    self.desynced_at = Some(frame);
+
+                    return Err(Event::Notify(format!(
+                        "Movie desynced at frame {}. Ensure {} is the selected ROM.",
+                        frame, self.movie.rom_name
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1138 @@
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, VecDeque};
+
+/// Address the compiled program is loaded at
+const PROGRAM_START: usize = 0x200;
+
+/// End of the memory of XO-Chip, the largest platform. Nothing can be written past it
+const MEMORY_END: usize = 0x10000;
+
+/// How deeply macros may expand inside other macros, so recursive macros fail to compile instead
+/// of expanding forever
+const MAX_MACRO_DEPTH: usize = 16;
+
+/// Compile Octo assembly into a ROM, which is loaded at 0x200.
+/// Octo is the assembly language used by the Octo IDE, in which Octo cartridges store programs.
+/// Execution starts at the label `main`
+pub fn compile(source: &str) -> Result<Vec<u8>> {
+    let mut compiler = Compiler::new(source);
+
+    // 0x200 jumps to main, which may be anywhere in the program. Like in Octo, the jump is left
+    // out again if main directly follows it
+    compiler.emit_address(0x1000, "main")?;
+
+    while let Some(token) = compiler.tokens.pop_front() {
+        compiler.statement(&token).map_err(|error| {
+            error.context(format!("Error compiling Octo program at '{}'", token))
+        })?;
+    }
+
+    compiler.finish()
+}
+
+struct Compiler {
+    tokens: VecDeque<String>,
+
+    /// The compiled program, starting at 0x200
+    rom: Vec<u8>,
+
+    /// Address the next byte is written to
+    here: usize,
+
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+
+    /// For each macro expansion in progress, the number of tokens left once it is consumed
+    expansions: Vec<usize>,
+
+    string_modes: HashMap<String, StringMode>,
+
+    /// References to labels, which were not defined yet when they were used
+    fixups: Vec<(String, usize, Fixup)>,
+
+    /// Addresses of the jumps of unfinished `if ... begin` blocks
+    branches: Vec<usize>,
+
+    /// Start addresses of unfinished loops, together with the jumps of their `while` conditions
+    loops: Vec<(usize, Vec<usize>)>,
+}
+
+/// Where and how an address is written into the program
+#[derive(Copy, Clone)]
+enum Fixup {
+    /// The lowest 12 bits of an instruction
+    Nnn,
+
+    /// 2 whole bytes
+    Long,
+
+    /// The high byte of `:unpack`, with the nibble in front of the address, if any
+    UnpackHigh(Option<u8>),
+
+    /// The low byte of `:unpack`
+    UnpackLow,
+}
+
+struct Macro {
+    arguments: Vec<String>,
+    body: Vec<String>,
+    calls: usize,
+}
+
+struct StringMode {
+    alphabet: Vec<char>,
+    body: Vec<String>,
+}
+
+enum Condition {
+    /// If the key in the register is pressed, or not pressed
+    Key(u8, bool),
+
+    Compare(u8, String, Operand),
+}
+
+enum Operand {
+    Register(u8),
+    Immediate(u8),
+}
+
+impl Compiler {
+    fn new(source: &str) -> Self {
+        let mut aliases = HashMap::new();
+        aliases.insert("unpack-hi".to_string(), 0);
+        aliases.insert("unpack-lo".to_string(), 1);
+        aliases.insert("compare-temp".to_string(), 0xF);
+
+        Self {
+            tokens: tokenize(source),
+            rom: Vec::new(),
+            here: PROGRAM_START,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases,
+            macros: HashMap::new(),
+            expansions: Vec::new(),
+            string_modes: HashMap::new(),
+            fixups: Vec::new(),
+            branches: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    fn statement(&mut self, token: &str) -> Result<()> {
+        match token {
+            ":" => {
+                let name = self.next()?;
+
+                if name == "main" && self.here == PROGRAM_START + 2 && self.rom.len() == 2 {
+                    self.rom.clear();
+                    self.here = PROGRAM_START;
+                    self.fixups
+                        .retain(|(label, address, _)| label != "main" || *address != PROGRAM_START);
+                }
+
+                self.define_label(name, self.here)?;
+            }
+            ":next" => {
+                // Label the second byte of the next instruction, for self modifying code
+                let name = self.next()?;
+                self.define_label(name, self.here + 1)?;
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = match self.next()?.as_str() {
+                    "{" => self.calc()? as u8 & 0xF,
+                    token => self.register(token)?,
+                };
+                self.aliases.insert(name, register);
+            }
+            ":unpack" => {
+                let nibble = match self.peek() {
+                    Some("long") => {
+                        self.next()?;
+                        None
+                    }
+                    _ => Some(self.value()? as u8 & 0xF),
+                };
+                let label = self.next()?;
+                let high = self.aliases["unpack-hi"] as u16;
+                let low = self.aliases["unpack-lo"] as u16;
+
+                self.emit_op(0x6000 | high << 8)?;
+                self.reference(&label, self.here - 1, Fixup::UnpackHigh(nibble))?;
+                self.emit_op(0x6000 | low << 8)?;
+                self.reference(&label, self.here - 1, Fixup::UnpackLow)?;
+            }
+            ":org" => {
+                let address = self.value()?;
+
+                if !(0f64..=MEMORY_END as f64).contains(&address) {
+                    return Err(anyhow!("Address {:#X} is out of memory", address as i64));
+                }
+
+                self.here = address as usize;
+            }
+            ":byte" => {
+                let value = self.immediate()?;
+                self.emit(value)?;
+            }
+            ":pointer" => {
+                let label = self.next()?;
+                self.emit_op(0)?;
+                self.reference(&label, self.here - 2, Fixup::Long)?;
+            }
+            ":call" => {
+                let label = self.next()?;
+                self.emit_address(0x2000, &label)?;
+            }
+            ":macro" => self.define_macro()?,
+            ":stringmode" => self.define_string_mode()?,
+            ":proto" | ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ":assert" => {
+                if self.peek().is_some_and(|token| token.starts_with('"')) {
+                    self.next()?;
+                }
+
+                self.expect("{")?;
+
+                if self.calc()? == 0f64 {
+                    return Err(anyhow!("Assertion failed"));
+                }
+            }
+
+            ";" | "return" => self.emit_op(0x00EE)?,
+            "clear" => self.emit_op(0x00E0)?,
+            "exit" => self.emit_op(0x00FD)?,
+            "lores" => self.emit_op(0x00FE)?,
+            "hires" => self.emit_op(0x00FF)?,
+            "scroll-down" => {
+                let amount = self.immediate()? as u16 & 0xF;
+                self.emit_op(0x00C0 | amount)?;
+            }
+            "scroll-up" => {
+                let amount = self.immediate()? as u16 & 0xF;
+                self.emit_op(0x00D0 | amount)?;
+            }
+            "scroll-right" => self.emit_op(0x00FB)?,
+            "scroll-left" => self.emit_op(0x00FC)?,
+            "audio" => self.emit_op(0xF002)?,
+            "plane" => {
+                let planes = self.immediate()? as u16 & 0xF;
+                self.emit_op(0xF001 | planes << 8)?;
+            }
+            "bcd" => self.register_op(0xF033)?,
+            "saveflags" => self.register_op(0xF075)?,
+            "loadflags" => self.register_op(0xF085)?,
+            "save" | "load" => {
+                let vx = self.next_register()? as u16;
+
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let vy = self.next_register()? as u16;
+                    let op = if token == "save" { 0x5002 } else { 0x5003 };
+                    self.emit_op(op | vx << 8 | vy << 4)?;
+                } else {
+                    let op = if token == "save" { 0xF055 } else { 0xF065 };
+                    self.emit_op(op | vx << 8)?;
+                }
+            }
+            "sprite" => {
+                let vx = self.next_register()? as u16;
+                let vy = self.next_register()? as u16;
+                let height = self.immediate()? as u16 & 0xF;
+                self.emit_op(0xD000 | vx << 8 | vy << 4 | height)?;
+            }
+            "jump" => {
+                let label = self.next()?;
+                self.emit_address(0x1000, &label)?;
+            }
+            "jump0" => {
+                let label = self.next()?;
+                self.emit_address(0xB000, &label)?;
+            }
+            "native" => {
+                let label = self.next()?;
+                self.emit_address(0x0000, &label)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let op = match token {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.register_op(op)?;
+            }
+            "i" => self.i_assignment()?,
+
+            "if" => {
+                let condition = self.condition()?;
+
+                match self.next()?.as_str() {
+                    "then" => self.emit_skip(&condition, false)?,
+                    "begin" => {
+                        self.emit_skip(&condition, true)?;
+                        self.branches.push(self.here);
+                        self.emit_op(0x1000)?;
+                    }
+                    token => return Err(anyhow!("Expected 'then' or 'begin', found '{}'", token)),
+                }
+            }
+            "else" => {
+                let branch = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| anyhow!("'else' without 'if'"))?;
+                self.branches.push(self.here);
+                self.emit_op(0x1000)?;
+                self.patch(branch, self.here, Fixup::Nnn)?;
+            }
+            "end" => {
+                let branch = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| anyhow!("'end' without 'if'"))?;
+                self.patch(branch, self.here, Fixup::Nnn)?;
+            }
+            "loop" => self.loops.push((self.here, Vec::new())),
+            "while" => {
+                let condition = self.condition()?;
+                self.emit_skip(&condition, true)?;
+
+                let here = self.here;
+                let (_, breaks) = self
+                    .loops
+                    .last_mut()
+                    .ok_or_else(|| anyhow!("'while' outside of a loop"))?;
+                breaks.push(here);
+
+                self.emit_op(0x1000)?;
+            }
+            "again" => {
+                let (start, breaks) = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| anyhow!("'again' without 'loop'"))?;
+
+                self.emit_op(0x1000)?;
+                self.patch(self.here - 2, start, Fixup::Nnn)?;
+
+                for address in breaks {
+                    self.patch(address, self.here, Fixup::Nnn)?;
+                }
+            }
+
+            _ if self.try_register(token).is_some() => {
+                let vx = self.register(token)?;
+                self.register_assignment(vx)?;
+            }
+            _ if self.macros.contains_key(token) => self.expand_macro(token)?,
+            _ if self.string_modes.contains_key(token) => self.expand_string_mode(token)?,
+            _ if self.try_value(token).is_some() && !self.labels.contains_key(token) => {
+                let value = self.try_value(token).unwrap();
+                self.emit(byte(value)?)?;
+            }
+
+            // Anything else calls a subroutine
+            _ => self.emit_address(0x2000, token)?,
+        }
+
+        Ok(())
+    }
+
+    fn i_assignment(&mut self) -> Result<()> {
+        match self.next()?.as_str() {
+            ":=" => match self.next()?.as_str() {
+                "hex" => self.register_op(0xF029),
+                "bighex" => self.register_op(0xF030),
+                "long" => {
+                    let label = self.next()?;
+                    self.emit_op(0xF000)?;
+                    self.emit_op(0)?;
+                    self.reference(&label, self.here - 2, Fixup::Long)
+                }
+                label => self.emit_address(0xA000, label),
+            },
+            "+=" => self.register_op(0xF01E),
+            token => Err(anyhow!("Unknown operator for i: '{}'", token)),
+        }
+    }
+
+    fn register_assignment(&mut self, vx: u8) -> Result<()> {
+        let vx = vx as u16;
+        let operator = self.next()?;
+        let source = self.next()?;
+
+        let op = match (operator.as_str(), self.try_register(&source)) {
+            (":=", Some(vy)) => 0x8000 | (vy as u16) << 4,
+            ("|=", Some(vy)) => 0x8001 | (vy as u16) << 4,
+            ("&=", Some(vy)) => 0x8002 | (vy as u16) << 4,
+            ("^=", Some(vy)) => 0x8003 | (vy as u16) << 4,
+            ("+=", Some(vy)) => 0x8004 | (vy as u16) << 4,
+            ("-=", Some(vy)) => 0x8005 | (vy as u16) << 4,
+            (">>=", Some(vy)) => 0x8006 | (vy as u16) << 4,
+            ("=-", Some(vy)) => 0x8007 | (vy as u16) << 4,
+            ("<<=", Some(vy)) => 0x800E | (vy as u16) << 4,
+
+            (":=", None) => match source.as_str() {
+                "key" => 0xF00A,
+                "delay" => 0xF007,
+                "random" => 0xC000 | self.immediate()? as u16,
+                _ => 0x6000 | self.immediate_from(&source)? as u16,
+            },
+            ("+=", None) => 0x7000 | self.immediate_from(&source)? as u16,
+            ("-=", None) => 0x7000 | self.immediate_from(&source)?.wrapping_neg() as u16,
+
+            _ => return Err(anyhow!("Invalid operands for '{}'", operator)),
+        };
+
+        self.emit_op(op | vx << 8)
+    }
+
+    fn condition(&mut self) -> Result<Condition> {
+        let vx = self.next_register()?;
+
+        match self.next()?.as_str() {
+            "key" => Ok(Condition::Key(vx, true)),
+            "-key" => Ok(Condition::Key(vx, false)),
+            operator @ ("==" | "!=" | "<" | ">" | "<=" | ">=") => {
+                let operand = self.next()?;
+                let operand = match self.try_register(&operand) {
+                    Some(vy) => Operand::Register(vy),
+                    None => Operand::Immediate(self.immediate_from(&operand)?),
+                };
+
+                Ok(Condition::Compare(vx, operator.to_string(), operand))
+            }
+            token => Err(anyhow!("Unknown conditional operator '{}'", token)),
+        }
+    }
+
+    /// Emit instructions which skip the next instruction if the condition is true, or if it is
+    /// false
+    fn emit_skip(&mut self, condition: &Condition, skip_if: bool) -> Result<()> {
+        match condition {
+            Condition::Key(vx, pressed) => {
+                let op = if *pressed == skip_if { 0xE09E } else { 0xE0A1 };
+                self.emit_op(op | (*vx as u16) << 8)
+            }
+
+            Condition::Compare(vx, operator, operand) if operator == "==" || operator == "!=" => {
+                let skip_if_equal = (operator == "==") == skip_if;
+                let vx = (*vx as u16) << 8;
+
+                match (operand, skip_if_equal) {
+                    (Operand::Immediate(value), true) => self.emit_op(0x3000 | vx | *value as u16),
+                    (Operand::Immediate(value), false) => self.emit_op(0x4000 | vx | *value as u16),
+                    (Operand::Register(vy), true) => self.emit_op(0x5000 | vx | (*vy as u16) << 4),
+                    (Operand::Register(vy), false) => self.emit_op(0x9000 | vx | (*vy as u16) << 4),
+                }
+            }
+
+            Condition::Compare(vx, operator, operand) => {
+                // Subtract in the temporary register, so that VF tells the result of the comparison
+                let temp = (self.aliases["compare-temp"] as u16) << 8;
+                let vx = (*vx as u16) << 4;
+
+                match operand {
+                    Operand::Immediate(value) => self.emit_op(0x6000 | temp | *value as u16)?,
+                    Operand::Register(vy) => self.emit_op(0x8000 | temp | (*vy as u16) << 4)?,
+                }
+
+                // VF is set if no borrow occurred, which means the condition is true for >= and <=
+                let flag_if_true = match operator.as_str() {
+                    ">" => {
+                        self.emit_op(0x8005 | temp | vx)?;
+                        false
+                    }
+                    "<=" => {
+                        self.emit_op(0x8005 | temp | vx)?;
+                        true
+                    }
+                    "<" => {
+                        self.emit_op(0x8007 | temp | vx)?;
+                        false
+                    }
+                    _ => {
+                        self.emit_op(0x8007 | temp | vx)?;
+                        true
+                    }
+                };
+
+                let skip_flag = flag_if_true == skip_if;
+                self.emit_op(0x3F00 | skip_flag as u16)
+            }
+        }
+    }
+
+    fn define_macro(&mut self) -> Result<()> {
+        let name = self.next()?;
+        let mut arguments = Vec::new();
+
+        loop {
+            match self.next()?.as_str() {
+                "{" => break,
+                argument => arguments.push(argument.to_string()),
+            }
+        }
+
+        let body = self.block()?;
+        self.macros.insert(
+            name,
+            Macro {
+                arguments,
+                body,
+                calls: 0,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, name: &str) -> Result<()> {
+        let argument_count = self.macros[name].arguments.len();
+        let mut values = Vec::new();
+
+        for _ in 0..argument_count {
+            values.push(self.next()?);
+        }
+
+        // A macro called last in another macro still counts as nested, to catch tail recursion
+        while self
+            .expansions
+            .last()
+            .is_some_and(|end| *end > self.tokens.len())
+        {
+            self.expansions.pop();
+        }
+
+        if self.expansions.len() >= MAX_MACRO_DEPTH {
+            return Err(anyhow!(
+                "Macros are nested more than {} levels deep",
+                MAX_MACRO_DEPTH
+            ));
+        }
+
+        self.expansions.push(self.tokens.len());
+
+        let definition = self.macros.get_mut(name).unwrap();
+        let calls = definition.calls.to_string();
+        definition.calls += 1;
+
+        let expanded: Vec<String> = definition
+            .body
+            .iter()
+            .map(|token| {
+                if token == "CALLS" {
+                    return calls.clone();
+                }
+
+                match definition
+                    .arguments
+                    .iter()
+                    .position(|argument| argument == token)
+                {
+                    Some(index) => values[index].clone(),
+                    None => token.clone(),
+                }
+            })
+            .collect();
+
+        for token in expanded.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+
+        Ok(())
+    }
+
+    fn define_string_mode(&mut self) -> Result<()> {
+        let name = self.next()?;
+        let alphabet = unquote(&self.next()?)?.chars().collect();
+        self.expect("{")?;
+        let body = self.block()?;
+
+        self.string_modes
+            .insert(name, StringMode { alphabet, body });
+
+        Ok(())
+    }
+
+    fn expand_string_mode(&mut self, name: &str) -> Result<()> {
+        let text = unquote(&self.next()?)?;
+        let mode = &self.string_modes[name];
+        let mut expanded = Vec::new();
+
+        for (index, char) in text.chars().enumerate() {
+            let value = mode
+                .alphabet
+                .iter()
+                .position(|letter| *letter == char)
+                .ok_or_else(|| anyhow!("String mode {} can't encode '{}'", name, char))?;
+
+            for token in &mode.body {
+                expanded.push(match token.as_str() {
+                    "CHAR" => (char as u32).to_string(),
+                    "INDEX" => index.to_string(),
+                    "VALUE" => value.to_string(),
+                    _ => token.clone(),
+                });
+            }
+        }
+
+        for token in expanded.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+
+        Ok(())
+    }
+
+    /// Collect the tokens up to the matching closing brace
+    fn block(&mut self) -> Result<Vec<String>> {
+        let mut depth = 0;
+        let mut body = Vec::new();
+
+        loop {
+            let token = self.next()?;
+
+            match token.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Ok(body),
+                "}" => depth -= 1,
+                _ => {}
+            }
+
+            body.push(token);
+        }
+    }
+
+    /// Evaluate a `:calc` expression, after the opening brace.
+    /// Like in Octo, operators have no precedence and are evaluated from right to left
+    fn calc(&mut self) -> Result<f64> {
+        let value = self.calc_expression()?;
+        self.expect("}")?;
+
+        Ok(value)
+    }
+
+    fn calc_expression(&mut self) -> Result<f64> {
+        let lhs = self.calc_term()?;
+
+        let Some(operator) = self.peek().filter(|token| is_binary_operator(token)) else {
+            return Ok(lhs);
+        };
+        let operator = operator.to_string();
+        self.next()?;
+
+        let rhs = self.calc_expression()?;
+        let (a, b) = (lhs as i64, rhs as i64);
+        let shift = |shift: fn(i64, u32) -> Option<i64>| {
+            u32::try_from(b)
+                .ok()
+                .and_then(|b| shift(a, b))
+                .map(|value| value as f64)
+                .ok_or_else(|| anyhow!("Can't shift by {}", b))
+        };
+
+        Ok(match operator.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => shift(i64::checked_shl)?,
+            ">>" => shift(i64::checked_shr)?,
+            "<" => (lhs < rhs) as u8 as f64,
+            "<=" => (lhs <= rhs) as u8 as f64,
+            "==" => (lhs == rhs) as u8 as f64,
+            "!=" => (lhs != rhs) as u8 as f64,
+            ">=" => (lhs >= rhs) as u8 as f64,
+            _ => (lhs > rhs) as u8 as f64,
+        })
+    }
+
+    fn calc_term(&mut self) -> Result<f64> {
+        let token = self.next()?;
+
+        match token.as_str() {
+            "(" => {
+                let value = self.calc_expression()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            "@" => {
+                let address = self.calc_term()? as usize;
+                Ok(self.read(address) as f64)
+            }
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => match self.try_value(&token) {
+                Some(value) => Ok(value),
+                None if UNARY_OPERATORS.contains(&token.as_str()) => {
+                    let value = self.calc_term()?;
+                    Ok(unary(&token, value))
+                }
+                None => Err(anyhow!("Unknown value '{}'", token)),
+            },
+        }
+    }
+
+    fn define_label(&mut self, name: String, address: usize) -> Result<()> {
+        if self.labels.contains_key(&name) {
+            return Err(anyhow!("Label {} is defined twice", name));
+        }
+
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    /// Emit an instruction with an address in its lowest 12 bits
+    fn emit_address(&mut self, op: u16, label: &str) -> Result<()> {
+        self.emit_op(op)?;
+        self.reference(label, self.here - 2, Fixup::Nnn)
+    }
+
+    /// Write the address of a label into the program, once the label is defined
+    fn reference(&mut self, label: &str, address: usize, fixup: Fixup) -> Result<()> {
+        let target = match self.labels.get(label) {
+            Some(target) => Some(*target),
+            None => self.try_value(label).map(|value| value as usize),
+        };
+
+        match target {
+            Some(target) => self.patch(address, target, fixup),
+            None => {
+                self.fixups.push((label.to_string(), address, fixup));
+                Ok(())
+            }
+        }
+    }
+
+    fn patch(&mut self, address: usize, target: usize, fixup: Fixup) -> Result<()> {
+        match fixup {
+            Fixup::Nnn => {
+                if target > 0xFFF {
+                    return Err(anyhow!("Address {:#X} doesn't fit into 12 bits", target));
+                }
+
+                let high = self.read(address) & 0xF0 | (target >> 8) as u8;
+                self.write(address, high)?;
+                self.write(address + 1, target as u8)
+            }
+            Fixup::Long => {
+                self.write(address, (target >> 8) as u8)?;
+                self.write(address + 1, target as u8)
+            }
+            Fixup::UnpackHigh(Some(nibble)) => {
+                self.write(address, nibble << 4 | (target >> 8) as u8 & 0xF)
+            }
+            Fixup::UnpackHigh(None) => self.write(address, (target >> 8) as u8),
+            Fixup::UnpackLow => self.write(address, target as u8),
+        }
+    }
+
+    fn register_op(&mut self, op: u16) -> Result<()> {
+        let vx = self.next_register()? as u16;
+        self.emit_op(op | vx << 8)
+    }
+
+    fn emit_op(&mut self, op: u16) -> Result<()> {
+        self.emit((op >> 8) as u8)?;
+        self.emit(op as u8)
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<()> {
+        self.write(self.here, byte)?;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, address: usize, byte: u8) -> Result<()> {
+        let index = address
+            .checked_sub(PROGRAM_START)
+            .ok_or_else(|| anyhow!("Can't write below {:#X}", PROGRAM_START))?;
+
+        if address >= MEMORY_END {
+            return Err(anyhow!("Can't write past {:#X}", MEMORY_END - 1));
+        }
+
+        if index >= self.rom.len() {
+            self.rom.resize(index + 1, 0);
+        }
+
+        self.rom[index] = byte;
+        Ok(())
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        address
+            .checked_sub(PROGRAM_START)
+            .and_then(|index| self.rom.get(index))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn next(&mut self) -> Result<String> {
+        self.tokens
+            .pop_front()
+            .ok_or_else(|| anyhow!("Unexpected end of program"))
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(String::as_str)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(anyhow!("Expected '{}', found '{}'", expected, token)),
+        }
+    }
+
+    fn try_register(&self, token: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register);
+        }
+
+        let digit = token.strip_prefix(['v', 'V'])?;
+
+        if digit.len() == 1 {
+            u8::from_str_radix(digit, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    fn register(&self, token: &str) -> Result<u8> {
+        self.try_register(token)
+            .ok_or_else(|| anyhow!("Expected a register, found '{}'", token))
+    }
+
+    fn next_register(&mut self) -> Result<u8> {
+        let token = self.next()?;
+        self.register(&token)
+    }
+
+    /// A number, constant or defined label
+    fn try_value(&self, token: &str) -> Option<f64> {
+        if let Some(value) = self.constants.get(token) {
+            return Some(*value);
+        }
+
+        if let Some(address) = self.labels.get(token) {
+            return Some(*address as f64);
+        }
+
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16).ok()?
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            i64::from_str_radix(binary, 2).ok()?
+        } else {
+            digits.parse().ok()?
+        };
+
+        Some(if negative { -value } else { value } as f64)
+    }
+
+    fn value(&mut self) -> Result<f64> {
+        let token = self.next()?;
+
+        if token == "{" {
+            return self.calc();
+        }
+
+        self.try_value(&token)
+            .ok_or_else(|| anyhow!("Expected a value, found '{}'", token))
+    }
+
+    fn immediate(&mut self) -> Result<u8> {
+        byte(self.value()?)
+    }
+
+    fn immediate_from(&mut self, token: &str) -> Result<u8> {
+        if token == "{" {
+            return byte(self.calc()?);
+        }
+
+        let value = self
+            .try_value(token)
+            .ok_or_else(|| anyhow!("Expected a value, found '{}'", token))?;
+        byte(value)
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        if !self.branches.is_empty() {
+            return Err(anyhow!("Missing 'end' of an 'if ... begin' block"));
+        }
+
+        if !self.loops.is_empty() {
+            return Err(anyhow!("Missing 'again' of a loop"));
+        }
+
+        for (label, address, fixup) in std::mem::take(&mut self.fixups) {
+            let target = *self
+                .labels
+                .get(&label)
+                .ok_or_else(|| anyhow!("Undefined label '{}' in Octo program", label))?;
+
+            self.patch(address, target, fixup)?;
+        }
+
+        Ok(self.rom)
+    }
+}
+
+fn byte(value: f64) -> Result<u8> {
+    let value = value as i64;
+
+    if !(-128..=255).contains(&value) {
+        return Err(anyhow!("Value {} doesn't fit into a byte", value));
+    }
+
+    Ok(value as u8)
+}
+
+const UNARY_OPERATORS: [&str; 13] = [
+    "-", "~", "!", "sin", "cos", "tan", "exp", "log", "abs", "sqrt", "sign", "ceil", "floor",
+];
+
+fn unary(operator: &str, value: f64) -> f64 {
+    match operator {
+        "-" => -value,
+        "~" => !(value as i64) as f64,
+        "!" => (value == 0f64) as u8 as f64,
+        "sin" => value.sin(),
+        "cos" => value.cos(),
+        "tan" => value.tan(),
+        "exp" => value.exp(),
+        "log" => value.ln(),
+        "abs" => value.abs(),
+        "sqrt" => value.sqrt(),
+        "sign" => value.signum(),
+        "ceil" => value.ceil(),
+        _ => value.floor(),
+    }
+}
+
+fn is_binary_operator(token: &str) -> bool {
+    [
+        "+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>", "pow", "min", "max", "<", "<=", "==",
+        "!=", ">=", ">",
+    ]
+    .contains(&token)
+}
+
+fn unquote(token: &str) -> Result<String> {
+    token
+        .strip_prefix('"')
+        .and_then(|token| token.strip_suffix('"'))
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Expected a string, found '{}'", token))
+}
+
+/// Split the source into whitespace separated tokens, without comments.
+/// Strings are kept together as one token, including their quotes
+fn tokenize(source: &str) -> VecDeque<String> {
+    let mut tokens = VecDeque::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        if char.is_whitespace() {
+            continue;
+        }
+
+        if char == '#' {
+            while chars.next_if(|char| *char != '\n').is_some() {}
+            continue;
+        }
+
+        let mut token = String::from(char);
+
+        if char == '"' {
+            while let Some(char) = chars.next() {
+                match char {
+                    '\\' => match chars.next() {
+                        Some('n') => token.push('\n'),
+                        Some('t') => token.push('\t'),
+                        Some(escaped) => token.push(escaped),
+                        None => {}
+                    },
+                    '"' => {
+                        token.push('"');
+                        break;
+                    }
+                    _ => token.push(char),
+                }
+            }
+        } else {
+            while let Some(char) = chars.next_if(|char| !char.is_whitespace()) {
+                token.push(char);
+            }
+        }
+
+        tokens.push_back(token);
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn main_at_the_start_has_no_jump() {
+        let rom = compile(": main v0 := 5 loop again").unwrap();
+
+        assert_eq!(rom, [0x60, 0x05, 0x12, 0x02]);
+    }
+
+    #[test]
+    fn main_after_data_is_jumped_to() {
+        let source = "
+            : sprite 0xFF 0x81
+            : main
+                i := sprite
+                v0 := 1
+                v1 := 2
+                sprite v0 v1 2
+                loop again
+        ";
+
+        assert_eq!(
+            compile(source).unwrap(),
+            [0x12, 0x04, 0xFF, 0x81, 0xA2, 0x02, 0x60, 0x01, 0x61, 0x02, 0xD0, 0x12, 0x12, 0x0C]
+        );
+    }
+
+    #[test]
+    fn conditions_compile_to_skips() {
+        let source = "
+            : main
+                if v0 == 3 then v1 := 1
+                if v2 != v3 begin v4 := 2 else v4 := 3 end
+        ";
+
+        assert_eq!(
+            compile(source).unwrap(),
+            [0x40, 0x03, 0x61, 0x01, 0x92, 0x30, 0x12, 0x0C, 0x64, 0x02, 0x12, 0x0E, 0x64, 0x03]
+        );
+    }
+
+    #[test]
+    fn loops_break_on_while() {
+        let source = "
+            : main
+                loop
+                    v0 += 1
+                    while v0 != 10
+                again
+        ";
+
+        assert_eq!(
+            compile(source).unwrap(),
+            [0x70, 0x01, 0x40, 0x0A, 0x12, 0x08, 0x12, 0x00]
+        );
+    }
+
+    #[test]
+    fn subroutines_are_called_by_name() {
+        let source = "
+            : main
+                draw
+                exit
+            : draw
+                clear
+            ;
+        ";
+
+        assert_eq!(
+            compile(source).unwrap(),
+            [0x22, 0x04, 0x00, 0xFD, 0x00, 0xE0, 0x00, 0xEE]
+        );
+    }
+
+    #[test]
+    fn constants_calc_and_macros_are_expanded() {
+        let source = "
+            :const SPEED 3
+            :calc DOUBLE { SPEED * 2 }
+            :macro move REGISTER { REGISTER += DOUBLE }
+            : main
+                move v1
+                move v2
+        ";
+
+        assert_eq!(compile(source).unwrap(), [0x71, 0x06, 0x72, 0x06]);
+    }
+
+    #[test]
+    fn recursive_macros_fail() {
+        assert!(compile(":macro forever { forever } : main forever").is_err());
+        assert!(compile(":macro grow { 1 grow 2 } : main grow").is_err());
+
+        let repeated = format!(":macro one {{ 1 }} : main {}", "one ".repeat(100));
+        assert_eq!(compile(&repeated).unwrap(), [1; 100]);
+    }
+
+    #[test]
+    fn org_moves_the_output() {
+        let rom = compile(": main jump end :org 0x208 : end ;").unwrap();
+
+        assert_eq!(rom, [0x12, 0x08, 0, 0, 0, 0, 0, 0, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn addresses_past_memory_fail() {
+        assert!(compile(": main ; :org 0xFFFFFFFF 1").is_err());
+        assert!(compile(": main ; :org 0xFFFF 1 2").is_err());
+        assert!(compile(": main ; :org 0xFFFE 1 2").is_ok());
+    }
+
+    #[test]
+    fn invalid_shifts_fail() {
+        assert!(compile(":calc X { 1 << 64 } : main").is_err());
+        assert!(compile(":calc X { 1 >> -1 } : main").is_err());
+        assert!(compile(":calc X { 1 << 4 } : main v0 := X").is_ok());
+    }
+
+    #[test]
+    fn loops_past_12_bits_fail() {
+        assert!(compile(": main jump start :org 0x1000 : start loop again").is_err());
+    }
+
+    #[test]
+    fn undefined_main_fails() {
+        assert!(compile(": start ;").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{anyhow, Result};
+use eframe::egui::Color32;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Colors used to draw the display.
+/// XO-Chip has 2 planes, so a pixel can be in 4 different states, each of which has its own color.
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "[String; 4]", try_from = "[String; 4]")]
+pub struct Palette {
+    /// Indexed by the pixel state. The least significant bit corresponds to the first plane
+    pub colors: [Color32; 4],
+}
+
+impl Palette {
+    pub const COLOR_NAMES: [&'static str; 4] = ["background", "plane1", "plane2", "both"];
+
+    pub const PRESETS: [(&'static str, Palette); 6] = [
+        (
+            "Default",
+            Palette::from_rgb([0x000000, 0xFFFFFF, 0x90EE90, 0x006400]),
+        ),
+        (
+            "Octo Classic",
+            Palette::from_rgb([0x996600, 0xFFCC00, 0xFF6600, 0x662200]),
+        ),
+        (
+            "LCD Green",
+            Palette::from_rgb([0x9BBC0F, 0x0F380F, 0x8BAC0F, 0x306230]),
+        ),
+        (
+            "Amber",
+            Palette::from_rgb([0x1A0F00, 0xFFB000, 0xB36B00, 0xFFE0A0]),
+        ),
+        (
+            "High Contrast",
+            Palette::from_rgb([0x000000, 0xFFFFFF, 0xFFFF00, 0x00FFFF]),
+        ),
+        (
+            "Colorblind Safe",
+            Palette::from_rgb([0x000000, 0xE69F00, 0x56B4E9, 0xF0E442]),
+        ),
+    ];
+
+    const fn from_rgb(colors: [u32; 4]) -> Self {
+        Self {
+            colors: [
+                rgb(colors[0]),
+                rgb(colors[1]),
+                rgb(colors[2]),
+                rgb(colors[3]),
+            ],
+        }
+    }
+
+    /// Get the color of a pixel from the state of both planes
+    pub fn color(&self, (plane1, plane2): (bool, bool)) -> Color32 {
+        self.colors[plane1 as usize | (plane2 as usize) << 1]
+    }
+
+    /// Parse a color in the `#RRGGBB` format. The `#` is optional
+    pub fn parse_color(text: &str) -> Result<Color32> {
+        let hex = text.trim().trim_start_matches('#');
+
+        match u32::from_str_radix(hex, 16) {
+            Ok(color) if hex.len() == 6 => Ok(rgb(color)),
+            _ => Err(anyhow!("Invalid color: {}", text.trim())),
+        }
+    }
+
+    /// Format a color in the `#RRGGBB` format
+    pub fn format_color(color: Color32) -> String {
+        format!("#{:02X}{:02X}{:02X}", color.r(), color.g(), color.b())
+    }
+
+    /// Get the name of the preset matching this palette, if there is one
+    pub fn preset_name(&self) -> Option<&'static str> {
+        Self::PRESETS
+            .iter()
+            .find(|(_, preset)| preset == self)
+            .map(|(name, _)| *name)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::PRESETS[0].1
+    }
+}
+
+const fn rgb(color: u32) -> Color32 {
+    Color32::from_rgb((color >> 16) as u8, (color >> 8) as u8, color as u8)
+}
+
+/// Palettes are exported as one `name = #RRGGBB` line per color
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, color) in Self::COLOR_NAMES.iter().zip(self.colors) {
+            writeln!(f, "{} = {}", name, Self::format_color(color))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Palette {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut colors = [None; 4];
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid line in palette: {}", line))?;
+
+            let index = Self::COLOR_NAMES
+                .iter()
+                .position(|color_name| *color_name == name.trim())
+                .ok_or_else(|| anyhow!("Unknown color in palette: {}", name.trim()))?;
+
+            let color = Self::parse_color(value)
+                .map_err(|_| anyhow!("Invalid color for {}: {}", name.trim(), value.trim()))?;
+
+            colors[index] = Some(color);
+        }
+
+        let mut palette = Palette::default();
+
+        for (index, color) in colors.into_iter().enumerate() {
+            palette.colors[index] = color.ok_or_else(|| {
+                anyhow!("Palette is missing the color {}", Self::COLOR_NAMES[index])
+            })?;
+        }
+
+        Ok(palette)
+    }
+}
+
+/// Palettes are saved in the settings as a list of `#RRGGBB` colors
+impl From<Palette> for [String; 4] {
+    fn from(palette: Palette) -> Self {
+        palette.colors.map(Palette::format_color)
+    }
+}
+
+impl TryFrom<[String; 4]> for Palette {
+    type Error = anyhow::Error;
+
+    fn try_from(colors: [String; 4]) -> Result<Self> {
+        let mut palette = Palette::default();
+
+        for (color, text) in palette.colors.iter_mut().zip(&colors) {
+            *color = Palette::parse_color(text)?;
+        }
+
+        Ok(palette)
+    }
+}
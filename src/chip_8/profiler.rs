@@ -0,0 +1,243 @@
+use crate::chip_8::disassembler;
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// Subroutine calls deeper than this aren't timed, so ROMs which never return don't grow the call
+/// stack forever
+const MAX_CALL_DEPTH: usize = 256;
+
+/// Counts the executed instructions, to find the hot spots of a ROM and how many instructions per
+/// frame it needs
+#[derive(Clone, Default)]
+pub struct Profiler {
+    /// Executed instructions in total
+    pub instructions: u64,
+
+    /// Executed instructions by address
+    pub addresses: HashMap<usize, AddressStats>,
+
+    /// Executed instructions by opcode class, like `DXYN`
+    pub classes: HashMap<&'static str, u64>,
+
+    /// Subroutines by their address
+    pub subroutines: HashMap<usize, SubroutineStats>,
+
+    /// How many frames needed each number of instructions before waiting, on the delay timer, on
+    /// a key press or in an endless loop
+    pub frame_instructions: BTreeMap<u32, u64>,
+
+    /// Address of each called subroutine, with the instruction count at the call
+    call_stack: Vec<(usize, u64)>,
+
+    /// Instructions executed in the current frame before waiting
+    current_frame: u32,
+    waiting: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct AddressStats {
+    pub opcode: u16,
+    pub count: u64,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct SubroutineStats {
+    pub calls: u64,
+
+    /// Instructions executed in the subroutine, including those of the subroutines it called
+    pub instructions: u64,
+}
+
+impl Profiler {
+    /// Record an executed instruction. `waiting` is whether the instruction waits for the delay
+    /// timer, a key press or loops endlessly
+    pub fn record_instruction(&mut self, address: usize, opcode: u16, waiting: bool) {
+        self.instructions += 1;
+
+        self.addresses
+            .entry(address)
+            .and_modify(|stats| {
+                stats.opcode = opcode;
+                stats.count += 1;
+            })
+            .or_insert(AddressStats { opcode, count: 1 });
+        *self.classes.entry(opcode_class(opcode)).or_default() += 1;
+
+        if !self.waiting {
+            self.current_frame += 1;
+            self.waiting = waiting;
+        }
+
+        if opcode & 0xF000 == 0x2000 {
+            if self.call_stack.len() < MAX_CALL_DEPTH {
+                self.call_stack
+                    .push(((opcode & 0x0FFF) as usize, self.instructions));
+            }
+        } else if opcode == 0x00EE {
+            if let Some((subroutine, start)) = self.call_stack.pop() {
+                let stats = self.subroutines.entry(subroutine).or_default();
+                stats.calls += 1;
+                stats.instructions += self.instructions - start;
+            }
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        *self
+            .frame_instructions
+            .entry(self.current_frame)
+            .or_default() += 1;
+
+        self.current_frame = 0;
+        self.waiting = false;
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frame_instructions.values().sum()
+    }
+
+    /// The number of instructions, which was enough for the fraction of frames
+    pub fn frame_percentile(&self, fraction: f64) -> u32 {
+        let target = (self.frames() as f64 * fraction).ceil() as u64;
+        let mut frames = 0;
+
+        for (&instructions, &count) in &self.frame_instructions {
+            frames += count;
+
+            if frames >= target {
+                return instructions;
+            }
+        }
+
+        0
+    }
+
+    pub fn average_frame_instructions(&self) -> f64 {
+        let total: u64 = self
+            .frame_instructions
+            .iter()
+            .map(|(&instructions, &count)| instructions as u64 * count)
+            .sum();
+
+        total as f64 / self.frames().max(1) as f64
+    }
+
+    /// Export all statistics as CSV, with a row per address, opcode class, subroutine and number of
+    /// instructions per frame
+    pub fn export_csv(&self, filepath: &Path) -> Result<()> {
+        let mut csv = String::from("kind,key,instruction,count,calls\n");
+
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by_key(|(address, _)| **address);
+
+        for (address, stats) in addresses {
+            let _ = writeln!(
+                csv,
+                "address,{:#05X},\"{}\",{},",
+                address,
+                disassembler::disassemble_opcode(stats.opcode),
+                stats.count
+            );
+        }
+
+        let mut classes: Vec<_> = self.classes.iter().collect();
+        classes.sort();
+
+        for (class, count) in classes {
+            let _ = writeln!(csv, "class,{},,{},", class, count);
+        }
+
+        let mut subroutines: Vec<_> = self.subroutines.iter().collect();
+        subroutines.sort_by_key(|(address, _)| **address);
+
+        for (address, stats) in subroutines {
+            let _ = writeln!(
+                csv,
+                "subroutine,{:#05X},,{},{}",
+                address, stats.instructions, stats.calls
+            );
+        }
+
+        for (instructions, frames) in &self.frame_instructions {
+            let _ = writeln!(csv, "frame,{},,{},", instructions, frames);
+        }
+
+        fs::write(filepath, csv).map_err(|error| {
+            anyhow!(error).context(format!("Error exporting profile to {}", filepath.display()))
+        })
+    }
+}
+
+/// The class of the opcode, as its pattern like `8XY4`
+pub fn opcode_class(opcode: u16) -> &'static str {
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => "00E0",
+            0x00EE => "00EE",
+            0x00FB => "00FB",
+            0x00FC => "00FC",
+            0x00FD => "00FD",
+            0x00FE => "00FE",
+            0x00FF => "00FF",
+            _ if opcode & 0xFFF0 == 0x00C0 => "00CN",
+            _ if opcode & 0xFFF0 == 0x00D0 => "00DN",
+            _ => "0NNN",
+        },
+        0x1000 => "1NNN",
+        0x2000 => "2NNN",
+        0x3000 => "3XNN",
+        0x4000 => "4XNN",
+        0x5000 => match opcode & 0x000F {
+            0x0 => "5XY0",
+            0x2 => "5XY2",
+            0x3 => "5XY3",
+            _ => "5XYN",
+        },
+        0x6000 => "6XNN",
+        0x7000 => "7XNN",
+        0x8000 => match opcode & 0x000F {
+            0x0 => "8XY0",
+            0x1 => "8XY1",
+            0x2 => "8XY2",
+            0x3 => "8XY3",
+            0x4 => "8XY4",
+            0x5 => "8XY5",
+            0x6 => "8XY6",
+            0x7 => "8XY7",
+            0xE => "8XYE",
+            _ => "8XYN",
+        },
+        0x9000 => "9XY0",
+        0xA000 => "ANNN",
+        0xB000 => "BNNN",
+        0xC000 => "CXNN",
+        0xD000 => "DXYN",
+        0xE000 => match opcode & 0x00FF {
+            0x9E => "EX9E",
+            0xA1 => "EXA1",
+            _ => "EXNN",
+        },
+        _ => match opcode & 0x00FF {
+            _ if opcode == 0xF000 => "F000",
+            0x01 => "FN01",
+            0x02 => "F002",
+            0x07 => "FX07",
+            0x0A => "FX0A",
+            0x15 => "FX15",
+            0x18 => "FX18",
+            0x1E => "FX1E",
+            0x29 => "FX29",
+            0x30 => "FX30",
+            0x33 => "FX33",
+            0x3A => "FX3A",
+            0x55 => "FX55",
+            0x65 => "FX65",
+            0x75 => "FX75",
+            0x85 => "FX85",
+            _ => "FXNN",
+        },
+    }
+}
@@ -0,0 +1,270 @@
+use crate::chip_8::config::Platform;
+use crate::chip_8::palette::Palette;
+use eframe::egui;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Bundled ROM database in the format of the community CHIP-8 database
+/// (https://github.com/chip-8/chip-8-database), with entries for the ROMs in `roms/`.
+/// Replacing the files with the ones of the community database recognizes far more ROMs.
+/// `sha1-hashes.json` maps the SHA-1 of a ROM to its program in `programs.json`
+const HASHES: &str = include_str!("../../assets/database/sha1-hashes.json");
+const PROGRAMS: &str = include_str!("../../assets/database/programs.json");
+
+static DATABASE: OnceLock<Database> = OnceLock::new();
+
+struct Database {
+    hashes: HashMap<String, usize>,
+    programs: Vec<Program>,
+}
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    description: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    release: Option<String>,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+struct Rom {
+    /// Platforms the ROM runs on, the most suitable first
+    #[serde(default)]
+    platforms: Vec<String>,
+
+    /// Instructions per frame
+    tickrate: Option<u32>,
+
+    colors: Option<Colors>,
+
+    /// Chip 8 keys used for the directions and buttons, keyed by names like `up` or `a`
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    /// Colors in the order of the pixel states, starting with the background
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// Everything known about a ROM from the database
+#[derive(Clone)]
+pub struct RomInfo {
+    pub title: String,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    pub platform: Option<Platform>,
+    pub tickrate: Option<u32>,
+    pub palette: Option<Palette>,
+
+    /// Keyboard keys the ROM suggests in addition to the regular keybindings
+    pub keys: Vec<(egui::Key, u8)>,
+}
+
+/// Get the hex encoded SHA-1 of ROM data, which identifies the ROM in the database
+pub fn hash(data: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
+
+/// Look up a ROM by the hex encoded SHA-1 of its data
+pub fn lookup(sha1: &str) -> Option<RomInfo> {
+    DATABASE
+        .get_or_init(|| Database::parse(HASHES, PROGRAMS).expect("Failed to parse ROM database."))
+        .lookup(sha1)
+}
+
+impl Database {
+    fn parse(hashes: &str, programs: &str) -> serde_json::Result<Self> {
+        Ok(Self {
+            hashes: serde_json::from_str(hashes)?,
+            programs: serde_json::from_str(programs)?,
+        })
+    }
+
+    fn lookup(&self, sha1: &str) -> Option<RomInfo> {
+        let program = self.programs.get(*self.hashes.get(sha1)?)?;
+        let rom = program.roms.get(sha1)?;
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            description: program.description.clone(),
+            authors: program.authors.clone(),
+            release: program.release.clone(),
+            platform: rom
+                .platforms
+                .iter()
+                .find_map(|platform| Platform::from_database_id(platform)),
+            tickrate: rom.tickrate,
+            palette: rom.colors.as_ref().and_then(palette),
+            keys: rom
+                .keys
+                .iter()
+                .filter_map(|(name, hex_key)| Some((keyboard_key(name)?, *hex_key & 0xF)))
+                .collect(),
+        })
+    }
+}
+
+/// Build a palette from the database colors. Colors which aren't given keep their default
+fn palette(colors: &Colors) -> Option<Palette> {
+    if colors.pixels.is_empty() {
+        return None;
+    }
+
+    let mut palette = Palette::default();
+
+    for (color, pixel) in palette.colors.iter_mut().zip(&colors.pixels) {
+        *color = Palette::parse_color(pixel).ok()?;
+    }
+
+    Some(palette)
+}
+
+fn keyboard_key(name: &str) -> Option<egui::Key> {
+    match name {
+        "up" => Some(egui::Key::ArrowUp),
+        "down" => Some(egui::Key::ArrowDown),
+        "left" => Some(egui::Key::ArrowLeft),
+        "right" => Some(egui::Key::ArrowRight),
+        "a" => Some(egui::Key::Space),
+        "b" => Some(egui::Key::Enter),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn database() -> Database {
+        let hashes = format!("{{\"{}\": 0}}", HASH);
+        let programs = format!(
+            r##"[{{
+                "title": "Test",
+                "authors": ["Someone"],
+                "roms": {{
+                    "{}": {{
+                        "platforms": ["superchip", "xochip"],
+                        "tickrate": 30,
+                        "colors": {{ "pixels": ["#000000", "#ff0000"] }},
+                        "keys": {{ "up": 5, "a": 22, "unknown": 1 }}
+                    }}
+                }}
+            }}]"##,
+            HASH
+        );
+
+        Database::parse(&hashes, &programs).unwrap()
+    }
+
+    #[test]
+    fn bundled_database_parses() {
+        let database = Database::parse(HASHES, PROGRAMS).unwrap();
+
+        for index in database.hashes.values() {
+            assert!(*index < database.programs.len());
+        }
+    }
+
+    #[test]
+    fn lookup_reads_the_rom_options() {
+        let info = database().lookup(HASH).unwrap();
+
+        assert_eq!(info.title, "Test");
+        assert_eq!(info.authors, vec!["Someone".to_string()]);
+        assert!(info.platform == Some(Platform::SuperChip));
+        assert_eq!(info.tickrate, Some(30));
+
+        let palette = info.palette.unwrap();
+        assert_eq!(palette.colors[0], egui::Color32::from_rgb(0, 0, 0));
+        assert_eq!(palette.colors[1], egui::Color32::from_rgb(255, 0, 0));
+        assert_eq!(palette.colors[2], Palette::default().colors[2]);
+
+        let mut keys = info.keys;
+        keys.sort_by_key(|(_, hex_key)| *hex_key);
+        assert_eq!(keys, vec![(egui::Key::ArrowUp, 5), (egui::Key::Space, 6)]);
+    }
+
+    #[test]
+    fn community_database_fields_are_accepted() {
+        // An entry using every field of the schema of the community database
+        let hashes = format!("{{\"{}\": 0}}", HASH);
+        let programs = format!(
+            r##"[{{
+                "title": "Test",
+                "origin": {{ "type": "gamejam", "reference": "Octojam 1" }},
+                "description": "A test program",
+                "release": "2014",
+                "copyright": "Someone",
+                "license": "MIT",
+                "authors": ["Someone"],
+                "images": ["test.png"],
+                "urls": ["https://example.com"],
+                "roms": {{
+                    "{}": {{
+                        "file": "test.ch8",
+                        "embeddedTitle": "TEST",
+                        "description": "The only release",
+                        "release": "2014-10-01",
+                        "platforms": ["xochip"],
+                        "quirkyPlatforms": {{ "xochip": {{ "shift": true, "wrap": false }} }},
+                        "authors": ["Someone"],
+                        "images": [],
+                        "urls": [],
+                        "tickrate": 1000,
+                        "startAddress": 512,
+                        "screenRotation": 0,
+                        "keys": {{ "left": 7, "right": 9, "b": 4, "player2Up": 1 }},
+                        "touchInputMode": "swipe",
+                        "fontStyle": "octo",
+                        "colors": {{
+                            "pixels": ["#111111", "#222222", "#333333", "#444444"],
+                            "buzzer": "#FFAA00",
+                            "silence": "#000000"
+                        }}
+                    }}
+                }}
+            }}]"##,
+            HASH
+        );
+
+        let info = Database::parse(&hashes, &programs)
+            .unwrap()
+            .lookup(HASH)
+            .unwrap();
+
+        assert_eq!(info.description.as_deref(), Some("A test program"));
+        assert_eq!(info.release.as_deref(), Some("2014"));
+        assert!(info.platform == Some(Platform::XoChip));
+        assert_eq!(info.tickrate, Some(1000));
+        assert_eq!(
+            info.palette.unwrap().colors[3],
+            egui::Color32::from_rgb(0x44, 0x44, 0x44)
+        );
+
+        let mut keys = info.keys;
+        keys.sort_by_key(|(_, hex_key)| *hex_key);
+        assert_eq!(
+            keys,
+            vec![
+                (egui::Key::Enter, 4),
+                (egui::Key::ArrowLeft, 7),
+                (egui::Key::ArrowRight, 9)
+            ]
+        );
+    }
+
+    #[test]
+    fn lookup_ignores_unknown_hashes() {
+        assert!(database().lookup(&hash(b"unknown")).is_none());
+    }
+}
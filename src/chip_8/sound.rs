@@ -1,48 +1,147 @@
-use rodio::queue;
-use rodio::source::SineWave;
-use rodio::{OutputStream, Sink};
+use crate::chip_8::audio::{self, AudioBackend, NullBackend, SampleBuffer};
+use crate::chip_8::config::{AudioOutput, SoundConfig, Waveform};
+use anyhow::{anyhow, Result};
+use hound::{SampleFormat, WavReader};
+use std::f32::consts::TAU;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use std::sync::mpsc::{self, Receiver, Sender};
+/// Sample rate the beeper is generated at
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// Samples generated for every emulated frame, which is 1/60 of a second
+pub const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / 60;
+
+/// How often the audio thread updates its backend
+const UPDATE_INTERVAL: Duration = Duration::from_millis(10);
 
 pub enum Command {
-    Play,
-    Pause,
     Stop,
     Sync(f32),
 }
 
+/// Generates the beeper from the sound timer of every emulated frame, so beeps last exactly as
+/// long as the emulated time. The samples are played by an `AudioBackend` on the audio thread.
+/// The beeper is silent until it is started
 #[derive(Clone)]
 pub struct Beeper {
-    tx: Sender<Command>,
+    tx: Option<Sender<Command>>,
+
+    /// Samples waiting to be played by the backend
+    buffer: Option<Arc<SampleBuffer>>,
+    output: AudioOutput,
+
+    /// Errors of the backend, after which the audio thread continues without output
+    errors: Option<Arc<Mutex<Receiver<anyhow::Error>>>>,
+
     volume: f32,
+    sound: Sound,
+    tone: Tone,
+}
+
+/// The sound of the beeper, with its custom sample decoded
+#[derive(Clone, Default)]
+pub struct Sound {
+    pub config: SoundConfig,
+
+    /// Mono samples at `SAMPLE_RATE`
+    sample: Option<Arc<Vec<f32>>>,
+}
+
+/// Generates the beeper sample by sample, fading in and out when it is switched on and off
+#[derive(Clone)]
+pub struct Tone {
+    sound: Sound,
+
+    /// Current volume of the envelope, from 0 to 1
+    gain: f32,
+
+    /// Position in the waveform, from 0 to 1
+    phase: f32,
+
+    /// Position in the custom sample
+    position: usize,
+
+    noise: f32,
+    noise_state: u32,
 }
 
 impl Default for Beeper {
     fn default() -> Self {
-        let (tx, rx) = mpsc::channel();
-        Self::spawn_thread(rx);
-
         Self {
-            tx,
+            tx: None,
+            buffer: None,
+            output: AudioOutput::None,
+            errors: None,
             volume: Self::DEFAULT_VOLUME,
+            sound: Sound::default(),
+            tone: Tone::new(Sound::default()),
         }
     }
 }
 
 impl Beeper {
-    const DEFAULT_VOLUME: f32 = 0.05;
-    const BEEP_FREQ: f32 = 440.0;
+    pub const DEFAULT_VOLUME: f32 = 0.05;
+
+    /// Start playing through the output, replacing the previous output. If the output can't be
+    /// opened, the beeper stays silent and the error is returned
+    pub fn start(&mut self, output: AudioOutput, filepath: PathBuf) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let (error_tx, error_rx) = mpsc::channel();
+        let buffer = Arc::new(SampleBuffer::new(audio::buffer_capacity(output)));
+        let backend_buffer = buffer.clone();
+
+        std::thread::spawn(move || {
+            // Backends may not be sendable to other threads, so this thread creates its own
+            let (backend, result) = match audio::create_backend(output, &filepath, backend_buffer) {
+                Ok(backend) => (backend, Ok(())),
+                Err(error) => (Box::new(NullBackend) as Box<dyn AudioBackend>, Err(error)),
+            };
 
-    pub fn play(&self) {
-        self.tx.send(Command::Play).unwrap();
+            let _ = result_tx.send(result);
+            Self::run_thread(backend, rx, error_tx);
+        });
+
+        self.tx = Some(tx);
+        self.buffer = Some(buffer);
+        self.output = output;
+        self.errors = Some(Arc::new(Mutex::new(error_rx)));
+        self.send(Command::Sync(self.volume));
+
+        result_rx
+            .recv()
+            .unwrap_or_else(|_| Err(anyhow!("The audio thread stopped unexpectedly")))
+    }
+
+    /// Generate the samples of an emulated frame, with the beeper on or off
+    pub fn push_frame(&mut self, on: bool) {
+        let Some(buffer) = &self.buffer else {
+            return;
+        };
+
+        let samples: Vec<f32> = (0..SAMPLES_PER_FRAME)
+            .map(|_| self.tone.next_sample(on))
+            .collect();
+        buffer.push(&samples);
     }
 
+    /// Silence the audio device right away when the emulation pauses, dropping the queued
+    /// samples. Other outputs keep them, so no emulated time is lost
     pub fn pause(&self) {
-        self.tx.send(Command::Pause).unwrap();
+        if self.output != AudioOutput::Device {
+            return;
+        }
+
+        if let Some(buffer) = &self.buffer {
+            buffer.clear();
+        }
     }
 
     pub fn stop(&self) {
-        self.tx.send(Command::Stop).unwrap();
+        self.send(Command::Stop);
     }
 
     pub fn get_volume(&self) -> f32 {
@@ -51,35 +150,186 @@ impl Beeper {
 
     pub fn set_volume(&mut self, volume: f32) {
         self.volume = volume;
-        self.tx.send(Command::Sync(volume)).unwrap();
+        self.send(Command::Sync(volume));
     }
 
-    fn spawn_thread(rx: Receiver<Command>) {
-        std::thread::spawn(move || {
-            let (queue, output_queue) = queue::queue(true);
-
-            if let Ok((_steam, stream_handle)) = OutputStream::try_default() {
-                let sink = Sink::try_new(&stream_handle).unwrap();
-                sink.append(output_queue);
-                sink.pause();
-                sink.set_volume(Self::DEFAULT_VOLUME);
-                queue.append(SineWave::new(Self::BEEP_FREQ));
-
-                loop {
-                    if let Ok(cmd) = rx.recv() {
-                        match cmd {
-                            Command::Play => sink.play(),
-                            Command::Pause => sink.pause(),
-                            Command::Sync(volume) => sink.set_volume(volume),
-
-                            Command::Stop => {
-                                sink.stop();
-                                break;
-                            }
+    pub fn get_sound(&self) -> &Sound {
+        &self.sound
+    }
+
+    pub fn set_sound(&mut self, sound: Sound) {
+        self.sound = sound.clone();
+        self.tone.set_sound(sound);
+    }
+
+    /// Take the next error of the output, which made the beeper go silent
+    pub fn poll_error(&self) -> Option<anyhow::Error> {
+        self.errors.as_ref()?.lock().unwrap().try_recv().ok()
+    }
+
+    /// Send a command to the audio thread. Commands are dropped if the beeper isn't started, or
+    /// was stopped
+    fn send(&self, command: Command) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(command);
+        }
+    }
+
+    fn run_thread(
+        mut backend: Box<dyn AudioBackend>,
+        rx: Receiver<Command>,
+        errors: Sender<anyhow::Error>,
+    ) {
+        loop {
+            match rx.recv_timeout(UPDATE_INTERVAL) {
+                Ok(Command::Sync(volume)) => backend.set_volume(volume),
+                Ok(Command::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+            if let Err(error) = backend.update() {
+                let _ = errors.send(error.context("Error playing the beeper, audio is disabled"));
+                backend = Box::new(NullBackend);
+            }
+        }
+    }
+}
+
+impl Sound {
+    /// Load the sound, decoding its custom sample
+    pub fn load(config: &SoundConfig) -> Result<Self> {
+        let sample = match &config.sample {
+            Some(filepath) => Some(Arc::new(read_sample(filepath).map_err(|error| {
+                error.context(format!(
+                    "Error reading beeper sample at {}",
+                    filepath.display()
+                ))
+            })?)),
+            None => None,
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            sample,
+        })
+    }
+}
+
+impl Tone {
+    pub fn new(sound: Sound) -> Self {
+        Self {
+            sound,
+            gain: 0f32,
+            phase: 0f32,
+            position: 0,
+            noise: 0f32,
+            noise_state: 0x1234_5678,
+        }
+    }
+
+    pub fn set_sound(&mut self, sound: Sound) {
+        self.sound = sound;
+    }
+
+    /// The next sample at `SAMPLE_RATE`, for the beeper being switched on or off
+    pub fn next_sample(&mut self, on: bool) -> f32 {
+        let config = &self.sound.config;
+
+        // Amount the gain changes per sample, to fade in or out over the given milliseconds
+        let step = |milliseconds: u32| 1000f32 / (milliseconds.max(1) as f32 * SAMPLE_RATE as f32);
+
+        if on {
+            self.gain = (self.gain + step(config.attack)).min(1f32);
+        } else {
+            self.gain = (self.gain - step(config.release)).max(0f32);
+        }
+
+        if self.gain == 0f32 {
+            // Every beep starts at the beginning of the waveform or sample
+            self.phase = 0f32;
+            self.position = 0;
+            return 0f32;
+        }
+
+        let value = match &self.sound.sample {
+            Some(sample) if !sample.is_empty() => {
+                let value = sample[self.position % sample.len()];
+                self.position += 1;
+                value
+            }
+
+            _ => {
+                let value = match config.waveform {
+                    Waveform::Square => {
+                        if self.phase < 0.5 {
+                            1f32
+                        } else {
+                            -1f32
                         }
                     }
+                    Waveform::Sine => (self.phase * TAU).sin(),
+                    Waveform::Triangle => 4f32 * (self.phase - 0.5).abs() - 1f32,
+                    Waveform::Noise => self.noise,
+                };
+
+                self.phase += config.frequency / SAMPLE_RATE as f32;
+
+                if self.phase >= 1f32 {
+                    self.phase %= 1f32;
+                    self.noise = self.next_noise();
                 }
+
+                value
             }
-        });
+        };
+
+        value * self.gain
+    }
+
+    /// Random value from -1 to 1, using xorshift
+    fn next_noise(&mut self) -> f32 {
+        let mut state = self.noise_state;
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        self.noise_state = state;
+
+        state as f32 / u32::MAX as f32 * 2f32 - 1f32
     }
 }
+
+/// Read a WAV file as mono samples at `SAMPLE_RATE`
+fn read_sample(filepath: &Path) -> Result<Vec<f32>> {
+    let mut reader = WavReader::open(filepath).map_err(|error| anyhow!(error))?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| anyhow!(error))?,
+        SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|error| anyhow!(error))?
+        }
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+
+    // Resample to the sample rate of the beeper, picking the nearest sample
+    let ratio = spec.sample_rate as f64 / SAMPLE_RATE as f64;
+    let length = (mono.len() as f64 / ratio) as usize;
+
+    Ok((0..length)
+        .map(|index| mono[((index as f64 * ratio) as usize).min(mono.len() - 1)])
+        .collect())
+}
@@ -0,0 +1,133 @@
+use crate::chip_8::config::{AudioOutput, EmulationOverrides, Platform, SoundConfig};
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::palette::Palette;
+use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+
+/// A Chip 8 Emulator written in rust.
+/// Options override the saved settings for this session only.
+#[derive(Parser, Default)]
+#[command(version)]
+pub struct Args {
+    /// ROM to start emulating right away, skipping the settings window
+    pub rom: Option<PathBuf>,
+
+    /// Use the quirks, memory size and speed of a platform:
+    /// originalChip8, modernChip8, superchip or xochip
+    #[arg(long, value_parser = parse_platform)]
+    pub platform: Option<Platform>,
+
+    #[arg(long, value_name = "BOOL")]
+    pub vf_reset: Option<bool>,
+
+    #[arg(long, value_name = "BOOL")]
+    pub increment_i_reg: Option<bool>,
+
+    #[arg(long, value_name = "BOOL")]
+    pub vx_offset_jump: Option<bool>,
+
+    #[arg(long, value_name = "BOOL")]
+    pub shift_vx_directly: Option<bool>,
+
+    #[arg(long, value_name = "BOOL")]
+    pub wrap_sprites: Option<bool>,
+
+    /// Instructions executed per frame
+    #[arg(long)]
+    pub ipf: Option<u32>,
+
+    /// Name of a palette preset, or path to an exported palette file
+    #[arg(long, value_parser = parse_palette)]
+    pub palette: Option<Palette>,
+
+    /// Initial window size, as the size of a single Chip 8 pixel
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    pub scale: u32,
+
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Load the ROM, but start with the emulation paused
+    #[arg(long)]
+    pub paused: bool,
+
+    /// Listen for GDB remote protocol connections at a TCP address like 127.0.0.1:9000,
+    /// a port on localhost, or the path of a Unix socket
+    #[arg(long, value_name = "ADDRESS")]
+    pub gdb: Option<String>,
+
+    /// Rhai script to run once the ROM is loaded
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Where the beeper is played: device, none or wav
+    #[arg(long, value_name = "OUTPUT", value_parser = parse_audio_output)]
+    pub audio: Option<AudioOutput>,
+
+    /// WAV file the beeper is written into. Implies --audio wav
+    #[arg(long, value_name = "FILE")]
+    pub audio_file: Option<PathBuf>,
+}
+
+impl Args {
+    /// Apply the options for the whole session. The emulation options take precedence over the
+    /// settings of every ROM loaded afterwards
+    pub fn apply(&self, emulator: &mut Emulator) {
+        emulator.set_emulation_overrides(EmulationOverrides {
+            platform: self.platform,
+            vf_reset: self.vf_reset,
+            increment_i_reg: self.increment_i_reg,
+            vx_offset_jump: self.vx_offset_jump,
+            shift_vx_directly: self.shift_vx_directly,
+            wrap_sprites: self.wrap_sprites,
+            instructions_per_frame: self.ipf,
+        });
+
+        // Kept apart from the saved palettes, and --fullscreen only affects the window
+        emulator.config.palette_override = self.palette;
+    }
+
+    /// The audio output and WAV file to use, from the options or else the saved settings
+    pub fn audio_output(&self, config: &SoundConfig) -> (AudioOutput, PathBuf) {
+        let output = match (self.audio, &self.audio_file) {
+            (Some(output), _) => output,
+            (None, Some(_)) => AudioOutput::WavFile,
+            (None, None) => config.output,
+        };
+
+        let filepath = self
+            .audio_file
+            .clone()
+            .unwrap_or_else(|| config.output_file.clone());
+
+        (output, filepath)
+    }
+}
+
+fn parse_platform(id: &str) -> Result<Platform, String> {
+    Platform::from_database_id(id).ok_or_else(|| format!("unknown platform '{}'", id))
+}
+
+fn parse_audio_output(value: &str) -> Result<AudioOutput, String> {
+    match value.to_lowercase().as_str() {
+        "device" => Ok(AudioOutput::Device),
+        "none" => Ok(AudioOutput::None),
+        "wav" => Ok(AudioOutput::WavFile),
+        _ => Err(format!("unknown audio output '{}'", value)),
+    }
+}
+
+fn parse_palette(value: &str) -> Result<Palette, String> {
+    if let Some((_, preset)) = Palette::PRESETS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+    {
+        return Ok(*preset);
+    }
+
+    let text = fs::read_to_string(value)
+        .map_err(|error| format!("no preset or readable palette file '{}': {}", value, error))?;
+
+    text.parse().map_err(|error| format!("{:#}", error))
+}
@@ -1,4 +1,11 @@
+use crate::capture::recording::Recording;
+use crate::capture::screenshot;
+use crate::chip_8::config::Speed;
 use crate::chip_8::emulator::Emulator;
+use crate::cli::Args;
+use crate::gdb_stub::GdbStub;
+use crate::saved_settings::SavedSettings;
+use crate::scripting::Script;
 use crate::ui::Screen;
 use crate::ui::MENU_BAR_OFFSET;
 use anyhow::Error;
@@ -6,6 +13,7 @@ use eframe::egui::{Context, FontId, ViewportCommand};
 use eframe::{egui, Frame};
 use std::cell::RefCell;
 use std::cmp::PartialEq;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::{Duration, Instant};
 
@@ -16,43 +24,114 @@ pub struct EmulatorApp {
     pub screen: Screen,
     pub state: AppState,
     pub frame_data: Rc<RefCell<FrameData>>,
+    pub recording: Option<Recording>,
+
+    /// Lets GDB frontends debug the emulation, if enabled on the command line
+    pub gdb: Option<GdbStub>,
+
+    /// Script which is run while emulating
+    pub script: Option<Script>,
 }
 
-impl Default for EmulatorApp {
-    fn default() -> Self {
-        let emulator = Rc::new(RefCell::new(Emulator::default()));
+impl EmulatorApp {
+    pub fn new(args: &Args) -> Self {
+        let mut emulator = Emulator::default();
+        let saved_settings =
+            SavedSettings::load().and_then(|saved_settings| saved_settings.apply(&mut emulator));
+
+        let (output, output_file) = args.audio_output(&emulator.config.sound);
+        let audio = emulator.beeper.start(output, output_file);
+
+        let state = Self::start(&mut emulator, args);
+
+        let emulator = Rc::new(RefCell::new(emulator));
         let frame_data = Rc::new(RefCell::new(FrameData::default()));
-        let screen = Screen::new(emulator.clone(), frame_data.clone());
+        let mut screen = Screen::new(emulator.clone(), frame_data.clone());
 
-        Self {
+        if let Err(error) = saved_settings.and(audio) {
+            screen.notify(format!("{:#}", error));
+        }
+
+        let gdb = match args.gdb.as_deref().map(GdbStub::listen) {
+            Some(Ok(gdb)) => {
+                screen.notify(format!("Listening for GDB at {}", gdb.address));
+                Some(gdb)
+            }
+            Some(Err(error)) => {
+                screen.notify(format!("{:#}", error));
+                None
+            }
+            None => None,
+        };
+
+        let mut app = Self {
             emulator,
             screen,
-            state: AppState::default(),
+            state,
             frame_data,
+            recording: None,
+            gdb,
+            script: None,
+        };
+
+        if let Some(filepath) = &args.script {
+            app.load_script(filepath.clone());
         }
+
+        app
     }
-}
 
-impl EmulatorApp {
-    pub fn run() {
+    /// Load the ROM given on the command line, and decide which state the app starts in
+    fn start(emulator: &mut Emulator, args: &Args) -> AppState {
+        // Applied before loading, so every ROM of the session uses the options
+        args.apply(emulator);
+
+        let Some(rom) = &args.rom else {
+            return AppState::default();
+        };
+
+        emulator.select_rom(rom.clone());
+
+        if let Err(error) = emulator.load_rom() {
+            return AppState::ErrorReporting(error);
+        }
+
+        if args.paused {
+            AppState::Paused
+        } else {
+            AppState::Emulating
+        }
+    }
+
+    pub fn run(args: Args) {
         eframe::run_native(
             "Chip 8 Emulator",
-            Self::options(),
-            Box::new(|cc| {
+            Self::options(&args),
+            Box::new(move |cc| {
                 cc.egui_ctx.style_mut(|style| {
                     style.override_font_id = Some(FontId::proportional(FONT_SIZE));
                 });
 
-                Ok(Box::new(Self::default()))
+                let app = Self::new(&args);
+
+                if args.fullscreen || app.emulator.borrow().config.fullscreen {
+                    cc.egui_ctx
+                        .send_viewport_cmd(ViewportCommand::Fullscreen(true));
+                }
+
+                Ok(Box::new(app))
             }),
         )
             .unwrap();
     }
 
-    fn options() -> eframe::NativeOptions {
+    fn options(args: &Args) -> eframe::NativeOptions {
+        let width = (64 * args.scale) as f32;
+        let height = (32 * args.scale) as f32;
+
         eframe::NativeOptions {
             viewport: egui::ViewportBuilder::default()
-                .with_inner_size([1280f32, 640f32 + MENU_BAR_OFFSET])
+                .with_inner_size([width, height + MENU_BAR_OFFSET])
                 .with_icon(
                     eframe::icon_data::from_png_bytes(include_bytes!("../assets/icon.png"))
                         .expect("Failed to load icon."),
@@ -61,36 +140,247 @@ impl EmulatorApp {
         }
     }
 
-    fn emulate(&self, ctx: &Context) -> Option<Event> {
-        self.frame_data.borrow_mut().wait_for_next_frame();
+    fn emulate(&mut self, ctx: &Context) -> Option<Event> {
+        let speed = {
+            let emulator = &mut *self.emulator.borrow_mut();
 
-        let emulator = &mut *self.emulator.borrow_mut();
+            let fast_forward = ctx.input(|input| {
+                emulator.keypad.update_keys(input);
+                input.key_down(emulator.keypad.hotkeys.fast_forward)
+            });
 
-        ctx.input(|input| {
-            emulator.keypad.update_keys(input);
-        });
+            if fast_forward {
+                emulator.config.fast_forward_speed
+            } else {
+                emulator.config.speed
+            }
+        };
+
+        self.frame_data.borrow_mut().speed = speed;
+
+        match speed {
+            Speed::Multiplier(multiplier) => {
+                let frames = {
+                    let frame_data = &mut *self.frame_data.borrow_mut();
+
+                    frame_data.wait_for_next_frame();
+                    frame_data.frames_due(multiplier)
+                };
+
+                for _ in 0..frames {
+                    if let Some(event) = self.run_frame() {
+                        return Some(event);
+                    }
+                }
+            }
+
+            Speed::Unlimited => {
+                let deadline = self.frame_data.borrow_mut().skip_wait();
 
-        emulator.tick_timers();
+                // Emulate frames until the time of a real frame is used up
+                loop {
+                    if let Some(event) = self.run_frame() {
+                        return Some(event);
+                    }
 
-        for _ in 0..emulator.config.instructions_per_frame {
-            if let Err(event) = emulator.execute_instruction() {
-                return Some(event);
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                }
             }
         }
 
         None
     }
 
+    /// Emulate a single frame, through the script if one is running, and add it to the recording,
+    /// if one is running
+    fn run_frame(&mut self) -> Option<Event> {
+        let result = match &mut self.script {
+            Some(script) => script.run_frame(&self.emulator),
+            None => self.emulator.borrow_mut().run_frame(),
+        };
+
+        if let Err(event) = result {
+            return Some(event);
+        }
+
+        if let Some(active_recording) = &mut self.recording {
+            if let Err(error) = active_recording.record_frame(&self.emulator.borrow()) {
+                self.recording = None;
+                return Some(Event::Notify(format!("Recording stopped: {:#}", error)));
+            }
+        }
+
+        None
+    }
+
+    fn check_hotkeys(&mut self, ctx: &Context) {
+        let hotkeys = self.emulator.borrow().keypad.hotkeys.clone();
+
+        let events: Vec<Event> = ctx.input(|input| {
+            [
+                (hotkeys.toggle_fullscreen, Event::ToggleFullscreen),
+                (hotkeys.screenshot, Event::TakeScreenshot),
+                (hotkeys.toggle_recording, Event::ToggleRecording),
+                (hotkeys.open_debugger, Event::OpenDebugger),
+            ]
+            .into_iter()
+            .filter(|(key, _)| input.key_pressed(*key))
+            .map(|(_, event)| event)
+            .collect()
+        });
+
+        for event in events {
+            self.on_event(event, ctx);
+        }
+    }
+
+    fn save_settings(&mut self) {
+        let result = SavedSettings::new(&self.emulator.borrow()).save();
+
+        if let Err(error) = result {
+            self.screen.notify(format!("{:#}", error));
+        }
+    }
+
+    fn load_script(&mut self, filepath: PathBuf) {
+        // Drop the previous script first, so its callbacks don't run
+        self.script = None;
+
+        match Script::load(&filepath, &self.emulator) {
+            Ok(script) => {
+                self.screen
+                    .notify(format!("Running script {}", filepath.display()));
+                self.script = Some(script);
+            }
+            Err(error) => self.screen.notify(format!("{:#}", error)),
+        }
+    }
+
+    fn notify_gdb(&mut self, notify: fn(&mut GdbStub)) {
+        if let Some(gdb) = &mut self.gdb {
+            notify(gdb);
+        }
+    }
+
+    /// Handle the requests of a connected GDB frontend
+    fn poll_gdb(&mut self, ctx: &Context) {
+        let event = match &mut self.gdb {
+            Some(gdb) => gdb.poll(&mut self.emulator.borrow_mut()),
+            None => None,
+        };
+
+        if let Some(event) = event {
+            self.on_event(event, ctx);
+        }
+    }
+
     fn on_event(&mut self, event: Event, ctx: &Context) {
         match event {
             Event::StartEmulation => {
+                if self.state == AppState::Debugging {
+                    self.emulator.borrow_mut().debugger.resume();
+                }
+
+                self.save_settings();
                 self.state = AppState::Emulating;
                 self.frame_data.borrow_mut().next_frame = Instant::now();
             },
 
-            Event::PauseEmulation => self.state = AppState::Paused,
-            Event::OpenSettings => self.state = AppState::Settings,
-            Event::ReportError(error) => self.state = AppState::ErrorReporting(error),
+            Event::PauseEmulation => {
+                self.notify_gdb(GdbStub::on_pause);
+                self.state = AppState::Paused;
+            }
+
+            Event::OpenSettings => {
+                self.notify_gdb(GdbStub::on_pause);
+                self.state = AppState::Settings;
+            }
+
+            Event::OpenDebugger => {
+                self.notify_gdb(GdbStub::on_pause);
+                self.emulator.borrow().beeper.pause();
+                self.state = AppState::Debugging;
+            }
+
+            Event::Break => {
+                self.notify_gdb(GdbStub::on_break);
+                self.state = AppState::Debugging;
+            }
+
+            Event::ToggleFullscreen => {
+                let config = &mut self.emulator.borrow_mut().config;
+
+                // The window may be fullscreen without the setting, when started with --fullscreen
+                let fullscreen = ctx.input(|input| input.viewport().fullscreen.unwrap_or(false));
+                config.fullscreen = !fullscreen;
+                ctx.send_viewport_cmd(ViewportCommand::Fullscreen(config.fullscreen));
+            }
+
+            Event::TakeScreenshot => {
+                let result = screenshot::save_screenshot(&self.emulator.borrow());
+
+                match result {
+                    Ok(filepath) => {
+                        self.screen
+                            .notify(format!("Saved screenshot to {}", filepath.display()));
+                    }
+                    Err(error) => self.screen.notify(format!("{:#}", error)),
+                }
+            }
+
+            Event::ToggleRecording => match self.recording.take() {
+                Some(recording) => match recording.finish() {
+                    Ok(path) => self
+                        .screen
+                        .notify(format!("Saved recording to {}", path.display())),
+                    Err(error) => self.screen.notify(format!("{:#}", error)),
+                },
+
+                None => {
+                    let result = Recording::start(&self.emulator.borrow());
+
+                    match result {
+                        Ok(recording) => {
+                            self.screen
+                                .notify(format!("Recording to {}", recording.path.display()));
+                            self.recording = Some(recording);
+                        }
+                        Err(error) => self.screen.notify(format!("{:#}", error)),
+                    }
+                }
+            },
+
+            Event::Notify(message) => self.screen.notify(message),
+
+            Event::LoadScript(filepath) => self.load_script(filepath),
+
+            Event::StopScript => {
+                if self.script.take().is_some() {
+                    self.screen.notify("Script stopped".to_string());
+                }
+            }
+
+            Event::ScriptError(error) => {
+                self.script = None;
+                self.screen.notify(format!("{:#}", error));
+            }
+
+            Event::MovieFinished => {
+                self.state = AppState::Paused;
+
+                if let Some(movie) = self.emulator.borrow_mut().movie.take() {
+                    self.screen.notify(match movie.desynced_at {
+                        Some(frame) => format!("Movie finished, desynced at frame {}", frame),
+                        None => "Movie finished without desyncs".to_string(),
+                    });
+                }
+            }
+            Event::ReportError(error) => {
+                self.notify_gdb(GdbStub::on_error);
+                self.state = AppState::ErrorReporting(error);
+            }
             Event::Exit => ctx.send_viewport_cmd(ViewportCommand::Close),
         }
     }
@@ -100,9 +390,16 @@ impl eframe::App for EmulatorApp {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
         ctx.request_repaint();
 
-        let event = self
-            .screen
-            .draw_main_screen(ctx, self.state == AppState::Paused);
+        self.check_hotkeys(ctx);
+        self.poll_gdb(ctx);
+
+        let audio_error = self.emulator.borrow().beeper.poll_error();
+        if let Some(error) = audio_error {
+            self.on_event(Event::Notify(format!("{:#}", error)), ctx);
+        }
+
+        let paused = matches!(self.state, AppState::Paused | AppState::Debugging);
+        let event = self.screen.draw_main_screen(ctx, paused);
 
         if let Some(event) = event {
             self.on_event(event, ctx);
@@ -111,6 +408,7 @@ impl eframe::App for EmulatorApp {
         let event = match &mut self.state {
             AppState::Emulating => self.emulate(ctx),
             AppState::Settings => self.screen.draw_settings(ctx),
+            AppState::Debugging => self.screen.draw_debugger(ctx),
             AppState::ErrorReporting(error) => self.screen.draw_error(ctx, error),
             AppState::Paused => None,
         };
@@ -119,12 +417,20 @@ impl eframe::App for EmulatorApp {
             self.on_event(event, ctx);
         }
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_settings();
+    }
 }
 
 pub enum AppState {
     Emulating,
     Paused,
     Settings,
+
+    /// Paused with the debugger open
+    Debugging,
+
     ErrorReporting(Error),
 }
 
@@ -144,13 +450,38 @@ pub enum Event {
     StartEmulation,
     PauseEmulation,
     OpenSettings,
+    OpenDebugger,
+
+    /// Emulation hit a breakpoint or watchpoint
+    Break,
+
+    ToggleFullscreen,
+    TakeScreenshot,
+    ToggleRecording,
+    Notify(String),
+    LoadScript(PathBuf),
+    StopScript,
+
+    /// The running script failed, and is stopped
+    ScriptError(Error),
+
+    MovieFinished,
     ReportError(Error),
     Exit,
 }
 
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
 pub struct FrameData {
     pub next_frame: Instant,
     pub sleep_time: Duration,
+
+    /// The speed which is currently emulated at
+    pub speed: Speed,
+
+    /// Emulated frames which did not add up to a whole frame yet.
+    /// Allows speeds below 1x, by only emulating a frame every few real frames
+    pub pending_frames: f32,
 }
 
 impl Default for FrameData {
@@ -158,17 +489,38 @@ impl Default for FrameData {
         Self {
             next_frame: Instant::now(),
             sleep_time: Duration::from_secs(0),
+            speed: Speed::default(),
+            pending_frames: 0f32,
         }
     }
 }
 
 impl FrameData {
     pub fn wait_for_next_frame(&mut self) {
-        self.next_frame += Duration::from_secs_f32(1f32 / 60f32);
+        self.next_frame += FRAME_DURATION;
 
         let sleep_time = self.next_frame - Instant::now();
         self.sleep_time = sleep_time;
 
         std::thread::sleep(sleep_time);
     }
+
+    /// Start the next frame immediately, without any FPS limiting.
+    /// Returns the time at which the frame should end
+    pub fn skip_wait(&mut self) -> Instant {
+        self.next_frame = Instant::now() + FRAME_DURATION;
+        self.sleep_time = Duration::from_secs(0);
+
+        self.next_frame
+    }
+
+    /// How many frames should be emulated in this real frame, to emulate at the given speed multiplier
+    pub fn frames_due(&mut self, multiplier: f32) -> u32 {
+        self.pending_frames += multiplier;
+
+        let frames = self.pending_frames.floor();
+        self.pending_frames -= frames;
+
+        frames as u32
+    }
 }
@@ -0,0 +1,735 @@
+use crate::chip_8::debugger::{Breakpoint, WatchKind, Watchpoint};
+use crate::chip_8::emulator::Emulator;
+use crate::emulator_app::Event;
+use anyhow::{anyhow, Result};
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// How long the connection thread waits for data, before it checks for replies to send
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+/// Register numbers after V0 - VF, in the order of the `g` packet
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+
+/// Serves the GDB remote serial protocol, so debugger frontends can control the emulator.
+///
+/// A thread accepts a single connection at a time and exchanges packets with it, while the
+/// packets are handled on the thread of the emulator when polled.
+/// Registers are V0 - VF, I, PC, SP, DT and ST, where I and PC are 16 bits in little endian.
+/// SP is the depth of the stack, since the stack isn't part of the emulated memory
+pub struct GdbStub {
+    /// Where the stub listens, for display
+    pub address: String,
+
+    requests: Receiver<Request>,
+    replies: Sender<String>,
+
+    /// Whether the frontend continued the emulation, and waits for it to stop
+    running: bool,
+}
+
+enum Request {
+    Packet(String),
+
+    /// The frontend wants to pause the emulation
+    Interrupt,
+
+    Disconnected,
+}
+
+enum Listener {
+    Tcp(TcpListener),
+
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl GdbStub {
+    /// Listen at a TCP address, like `127.0.0.1:9000`, a TCP port on localhost, or on Unix at the
+    /// path of a socket
+    pub fn listen(address: &str) -> Result<Self> {
+        let listener = Self::bind(address).map_err(|error| {
+            anyhow!(error).context(format!("Error listening for GDB at {}", address))
+        })?;
+
+        let (request_tx, requests) = mpsc::channel();
+        let (replies, reply_rx) = mpsc::channel();
+
+        thread::spawn(move || serve(listener, request_tx, reply_rx));
+
+        Ok(Self {
+            address: address.to_string(),
+            requests,
+            replies,
+            running: false,
+        })
+    }
+
+    fn bind(address: &str) -> std::io::Result<Listener> {
+        if let Ok(port) = address.parse::<u16>() {
+            return TcpListener::bind(("127.0.0.1", port)).map(Listener::Tcp);
+        }
+
+        #[cfg(unix)]
+        if !address.contains(':') {
+            // Remove the socket of a previous session, which would make binding fail. Other files
+            // are never removed, in case the path is a typo
+            if let Ok(metadata) = std::fs::symlink_metadata(address) {
+                if !metadata.file_type().is_socket() {
+                    return Err(std::io::Error::new(
+                        ErrorKind::AlreadyExists,
+                        "A file which isn't a socket exists at the path",
+                    ));
+                }
+
+                std::fs::remove_file(address)?;
+            }
+
+            return UnixListener::bind(address).map(Listener::Unix);
+        }
+
+        TcpListener::bind(address).map(Listener::Tcp)
+    }
+
+    /// Handle the packets received since the last poll.
+    /// Returns an event, if the frontend paused or continued the emulation
+    pub fn poll(&mut self, emulator: &mut Emulator) -> Option<Event> {
+        loop {
+            let request = match self.requests.try_recv() {
+                Ok(request) => request,
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return None,
+            };
+
+            let event = match request {
+                Request::Packet(packet) => self.handle_packet(emulator, &packet),
+
+                Request::Interrupt => {
+                    self.on_pause();
+                    Some(Event::OpenDebugger)
+                }
+
+                Request::Disconnected => {
+                    self.running = false;
+                    None
+                }
+            };
+
+            if event.is_some() {
+                return event;
+            }
+        }
+    }
+
+    /// Tell the frontend the emulation stopped at a breakpoint or watchpoint
+    pub fn on_break(&mut self) {
+        self.on_stop(SIGTRAP);
+    }
+
+    /// Tell the frontend the emulation stopped, since an instruction failed
+    pub fn on_error(&mut self) {
+        self.on_stop(SIGILL);
+    }
+
+    /// Tell the frontend the emulation was paused
+    pub fn on_pause(&mut self) {
+        self.on_stop(SIGINT);
+    }
+
+    /// Send a stop reply, if the frontend waits for one
+    fn on_stop(&mut self, signal: u8) {
+        if self.running {
+            self.running = false;
+            self.reply(format!("S{:02x}", signal));
+        }
+    }
+
+    fn reply(&self, data: String) {
+        // The connection thread only stops with the app
+        let _ = self.replies.send(data);
+    }
+
+    fn handle_packet(&mut self, emulator: &mut Emulator, packet: &str) -> Option<Event> {
+        let command = packet.get(..1).unwrap_or_default();
+        let arguments = packet.get(1..).unwrap_or_default();
+
+        let (reply, event) = match command {
+            // Reason the emulation stopped. Asked when connecting, so pause the emulation
+            "?" => (format!("S{:02x}", SIGTRAP), Some(Event::OpenDebugger)),
+
+            "g" => (read_registers(emulator), None),
+            "G" => (reply_result(write_registers(emulator, arguments)), None),
+            "p" => (reply_result(read_register(emulator, arguments)), None),
+            "P" => (reply_result(write_register(emulator, arguments)), None),
+            "m" => (reply_result(read_memory(emulator, arguments)), None),
+            "M" => (reply_result(write_memory(emulator, arguments)), None),
+            "Z" => (
+                reply_result(set_breakpoint(emulator, arguments, true)),
+                None,
+            ),
+            "z" => (
+                reply_result(set_breakpoint(emulator, arguments, false)),
+                None,
+            ),
+
+            "s" => {
+                if let Err(error) = jump_to(emulator, arguments) {
+                    (error, None)
+                } else {
+                    match emulator.step() {
+                        Ok(()) | Err(Event::Break) => (format!("S{:02x}", SIGTRAP), None),
+
+                        // Running past the end of memory would close the app
+                        Err(Event::Exit) => (format!("S{:02x}", SIGILL), None),
+                        Err(event) => (format!("S{:02x}", SIGILL), Some(event)),
+                    }
+                }
+            }
+
+            "c" => {
+                if let Err(error) = jump_to(emulator, arguments) {
+                    (error, None)
+                } else if !emulator.rom_loaded {
+                    ("E01".to_string(), None)
+                } else {
+                    // Reply once the emulation stops again
+                    self.running = true;
+                    return Some(Event::StartEmulation);
+                }
+            }
+
+            "D" => {
+                self.running = false;
+                let event = emulator.rom_loaded.then_some(Event::StartEmulation);
+
+                ("OK".to_string(), event)
+            }
+
+            // Killing would close the emulator, so only stop debugging
+            "k" => {
+                self.running = false;
+                return None;
+            }
+
+            "H" => ("OK".to_string(), None),
+            "q" => (query(arguments), None),
+
+            // Unsupported packets get an empty reply
+            _ => (String::new(), None),
+        };
+
+        self.reply(reply);
+        event
+    }
+}
+
+/// Answer general queries
+fn query(query: &str) -> String {
+    if query.starts_with("Supported") {
+        return "PacketSize=1000;qXfer:features:read+".to_string();
+    }
+
+    if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+        return read_target_description(range).unwrap_or_else(|| "E01".to_string());
+    }
+
+    match query {
+        "Attached" => "1".to_string(),
+        "C" => "QC1".to_string(),
+        "fThreadInfo" => "m1".to_string(),
+        "sThreadInfo" => "l".to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Read part of the target description, which tells the frontend the registers
+fn read_target_description(range: &str) -> Option<String> {
+    let (offset, length) = parse_pair(range)?;
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+        <target version=\"1.0\"><feature name=\"org.chip8.core\">",
+    );
+
+    for reg in 0..16 {
+        let _ = write!(
+            xml,
+            "<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\"/>",
+            reg
+        );
+    }
+
+    xml.push_str(
+        "<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>\
+        <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>\
+        <reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>\
+        <reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>\
+        <reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>\
+        </feature></target>",
+    );
+
+    let start = offset.min(xml.len());
+    let end = offset.saturating_add(length).min(xml.len());
+    let more = if end < xml.len() { "m" } else { "l" };
+
+    Some(format!("{}{}", more, &xml[start..end]))
+}
+
+fn reply_result(result: Result<String, String>) -> String {
+    result.unwrap_or_else(|error| error)
+}
+
+fn read_registers(emulator: &Emulator) -> String {
+    (0..=REG_ST)
+        .map(|reg| encode_register(emulator, reg))
+        .collect()
+}
+
+fn write_registers(emulator: &mut Emulator, data: &str) -> Result<String, String> {
+    let bytes = decode_hex(data)?;
+    let mut offset = 0;
+
+    for reg in 0..=REG_ST {
+        let size = register_size(reg);
+        let value = bytes.get(offset..offset + size).ok_or("E01")?;
+
+        set_register(emulator, reg, value)?;
+        offset += size;
+    }
+
+    Ok("OK".to_string())
+}
+
+fn read_register(emulator: &Emulator, arguments: &str) -> Result<String, String> {
+    let reg = usize::from_str_radix(arguments, 16).map_err(|_| "E01")?;
+
+    if reg > REG_ST {
+        return Err("E01".to_string());
+    }
+
+    Ok(encode_register(emulator, reg))
+}
+
+fn write_register(emulator: &mut Emulator, arguments: &str) -> Result<String, String> {
+    let (reg, value) = arguments.split_once('=').ok_or("E01")?;
+    let reg = usize::from_str_radix(reg, 16).map_err(|_| "E01")?;
+    let value = decode_hex(value)?;
+
+    if reg > REG_ST || value.len() != register_size(reg) {
+        return Err("E01".to_string());
+    }
+
+    set_register(emulator, reg, &value)?;
+    Ok("OK".to_string())
+}
+
+fn register_size(reg: usize) -> usize {
+    match reg {
+        REG_I | REG_PC => 2,
+        _ => 1,
+    }
+}
+
+fn encode_register(emulator: &Emulator, reg: usize) -> String {
+    match reg {
+        REG_I => encode_hex(&(emulator.i_reg as u16).to_le_bytes()),
+        REG_PC => encode_hex(&(emulator.pc as u16).to_le_bytes()),
+        REG_SP => format!("{:02x}", emulator.stack.len() as u8),
+        REG_DT => format!("{:02x}", emulator.delay_timer),
+        REG_ST => format!("{:02x}", emulator.sound_timer),
+        _ => format!("{:02x}", emulator.v_regs[reg]),
+    }
+}
+
+fn set_register(emulator: &mut Emulator, reg: usize, value: &[u8]) -> Result<(), String> {
+    match reg {
+        REG_I => emulator.i_reg = u16::from_le_bytes([value[0], value[1]]) as usize,
+        REG_PC => {
+            emulator.pc = valid_pc(emulator, u16::from_le_bytes([value[0], value[1]]) as usize)?
+        }
+        REG_SP => emulator.stack.resize(value[0] as usize, 0),
+        REG_DT => emulator.delay_timer = value[0],
+        REG_ST => emulator.sound_timer = value[0],
+        _ => emulator.v_regs[reg] = value[0],
+    }
+
+    Ok(())
+}
+
+fn read_memory(emulator: &Emulator, arguments: &str) -> Result<String, String> {
+    let (address, length) = parse_pair(arguments).ok_or("E01")?;
+    let end = address.checked_add(length).ok_or("E01")?;
+    let data = emulator.memory.data.get(address..end).ok_or("E01")?;
+
+    Ok(encode_hex(data))
+}
+
+fn write_memory(emulator: &mut Emulator, arguments: &str) -> Result<String, String> {
+    let (range, data) = arguments.split_once(':').ok_or("E01")?;
+    let (address, length) = parse_pair(range).ok_or("E01")?;
+    let data = decode_hex(data)?;
+
+    if data.len() != length {
+        return Err("E01".to_string());
+    }
+
+    let end = address.checked_add(length).ok_or("E01")?;
+    emulator
+        .memory
+        .data
+        .get_mut(address..end)
+        .ok_or("E01")?
+        .copy_from_slice(&data);
+
+    Ok("OK".to_string())
+}
+
+/// Insert or remove a breakpoint with `Z0`/`Z1`, or a write, read or access watchpoint with
+/// `Z2`, `Z3` or `Z4`
+fn set_breakpoint(
+    emulator: &mut Emulator,
+    arguments: &str,
+    insert: bool,
+) -> Result<String, String> {
+    let mut parts = arguments.split(',');
+    let kind = parts.next().ok_or("E01")?;
+    let address = parts
+        .next()
+        .and_then(|address| usize::from_str_radix(address, 16).ok())
+        .ok_or("E01")?;
+    let length = parts
+        .next()
+        .and_then(|length| usize::from_str_radix(length, 16).ok())
+        .unwrap_or(1)
+        .max(1);
+
+    let debugger = &mut emulator.debugger;
+
+    let watch_kind = match kind {
+        "0" | "1" => {
+            if insert {
+                debugger.breakpoints.insert(address, Breakpoint::default());
+            } else {
+                debugger.breakpoints.remove(&address);
+            }
+
+            return Ok("OK".to_string());
+        }
+
+        "2" => WatchKind::Write,
+        "3" => WatchKind::Read,
+        "4" => WatchKind::Access,
+        _ => return Ok(String::new()),
+    };
+
+    let watchpoint = Watchpoint {
+        start: address,
+        end: address.checked_add(length - 1).ok_or("E01")?,
+        kind: watch_kind,
+    };
+
+    if insert {
+        debugger.watchpoints.push(watchpoint);
+    } else {
+        debugger
+            .watchpoints
+            .retain(|existing| *existing != watchpoint);
+    }
+
+    Ok("OK".to_string())
+}
+
+/// Set the program counter to the optional address of a `c` or `s` packet
+fn jump_to(emulator: &mut Emulator, address: &str) -> Result<(), String> {
+    if !address.is_empty() {
+        let address = usize::from_str_radix(address, 16).map_err(|_| "E01")?;
+        emulator.pc = valid_pc(emulator, address)?;
+    }
+
+    Ok(())
+}
+
+/// Check that a whole instruction can be fetched at the address
+fn valid_pc(emulator: &Emulator, address: usize) -> Result<usize, String> {
+    if address < emulator.memory.size.saturating_sub(1) {
+        Ok(address)
+    } else {
+        Err("E01".to_string())
+    }
+}
+
+/// Parse two hex numbers separated by a comma, like the address and length of `m`
+fn parse_pair(text: &str) -> Option<(usize, usize)> {
+    let (first, second) = text.split_once(',')?;
+
+    Some((
+        usize::from_str_radix(first, 16).ok()?,
+        usize::from_str_radix(second, 16).ok()?,
+    ))
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, String> {
+    // Packets may contain any bytes, which can't be sliced in pairs unless they are ASCII
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        return Err("E01".to_string());
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&text[index..index + 2], 16).map_err(|_| "E01".to_string()))
+        .collect()
+}
+
+fn serve(listener: Listener, requests: Sender<Request>, replies: Receiver<String>) {
+    loop {
+        let result = match &listener {
+            Listener::Tcp(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_read_timeout(Some(POLL_INTERVAL))?;
+                stream.set_nodelay(true)?;
+                Ok(serve_connection(stream, &requests, &replies))
+            }),
+
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_read_timeout(Some(POLL_INTERVAL))?;
+                Ok(serve_connection(stream, &requests, &replies))
+            }),
+        };
+
+        // Stop once the app is gone
+        if let Ok(false) = result {
+            return;
+        }
+
+        if requests.send(Request::Disconnected).is_err() {
+            return;
+        }
+    }
+}
+
+/// Exchange packets with the frontend until it disconnects.
+/// Returns false, if the app is gone
+fn serve_connection<S: Read + Write>(
+    mut stream: S,
+    requests: &Sender<Request>,
+    replies: &Receiver<String>,
+) -> bool {
+    // Drop replies meant for a previous connection
+    while replies.try_recv().is_ok() {}
+
+    let mut packet: Option<Vec<u8>> = None;
+    let mut checksum: Option<Vec<u8>> = None;
+    let mut buffer = [0; 1024];
+
+    loop {
+        loop {
+            match replies.try_recv() {
+                Ok(reply) => {
+                    if write_packet(&mut stream, &reply).is_err() {
+                        return true;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+
+        let length = match stream.read(&mut buffer) {
+            Ok(0) => return true,
+            Ok(length) => length,
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                continue
+            }
+            Err(_) => return true,
+        };
+
+        for &byte in &buffer[..length] {
+            if let Some(digits) = &mut checksum {
+                digits.push(byte);
+
+                if digits.len() < 2 {
+                    continue;
+                }
+
+                let data = packet.take().unwrap_or_default();
+                let valid = std::str::from_utf8(digits)
+                    .ok()
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    == Some(data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)));
+                checksum = None;
+
+                if stream.write_all(if valid { b"+" } else { b"-" }).is_err() {
+                    return true;
+                }
+
+                if valid {
+                    let request = Request::Packet(String::from_utf8_lossy(&data).into_owned());
+
+                    if requests.send(request).is_err() {
+                        return false;
+                    }
+                }
+            } else if let Some(data) = &mut packet {
+                if byte == b'#' {
+                    checksum = Some(Vec::new());
+                } else {
+                    data.push(byte);
+                }
+            } else if byte == b'$' {
+                packet = Some(Vec::new());
+            } else if byte == 0x03 && requests.send(Request::Interrupt).is_err() {
+                return false;
+            }
+
+            // Acknowledgements of the frontend are ignored
+        }
+    }
+}
+
+fn write_packet<S: Write>(stream: &mut S, data: &str) -> std::io::Result<()> {
+    let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+
+    stream.write_all(format!("${}#{:02x}", data, checksum).as_bytes())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip_8::emulator::INSTRUCTIONS_START;
+
+    #[test]
+    fn parse_pair_reads_hex_numbers() {
+        assert_eq!(parse_pair("200,1f"), Some((0x200, 0x1F)));
+        assert_eq!(parse_pair("200"), None);
+        assert_eq!(parse_pair("200,"), None);
+        assert_eq!(parse_pair("x,1"), None);
+    }
+
+    #[test]
+    fn decode_hex_reads_pairs() {
+        assert_eq!(decode_hex("00ff1a"), Ok(vec![0x00, 0xFF, 0x1A]));
+        assert_eq!(decode_hex(""), Ok(Vec::new()));
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("zz").is_err());
+    }
+
+    #[test]
+    fn decode_hex_rejects_non_ascii() {
+        let text = String::from_utf8_lossy(b"\xff0").into_owned();
+
+        assert_eq!(text.len() % 2, 0);
+        assert!(decode_hex(&text).is_err());
+    }
+
+    #[test]
+    fn memory_ranges_past_the_address_space_fail() {
+        let mut emulator = Emulator::default();
+
+        assert_eq!(
+            read_memory(&emulator, "ffffffffffffffff,2"),
+            Err("E01".to_string())
+        );
+        assert_eq!(
+            write_memory(&mut emulator, "ffffffffffffffff,1:00"),
+            Err("E01".to_string())
+        );
+        assert_eq!(read_memory(&emulator, "200,2"), Ok("0000".to_string()));
+    }
+
+    #[test]
+    fn program_counter_must_be_in_memory() {
+        let mut emulator = Emulator::default();
+        let size = emulator.memory.size;
+
+        assert_eq!(jump_to(&mut emulator, "fff"), Err("E01".to_string()));
+        assert_eq!(
+            write_register(&mut emulator, "11=ff0f"),
+            Err("E01".to_string())
+        );
+        assert_eq!(emulator.pc, INSTRUCTIONS_START);
+
+        assert_eq!(jump_to(&mut emulator, &format!("{:x}", size - 2)), Ok(()));
+        assert_eq!(emulator.pc, size - 2);
+        assert_eq!(
+            write_register(&mut emulator, "11=0003"),
+            Ok("OK".to_string())
+        );
+        assert_eq!(emulator.pc, 0x300);
+    }
+
+    #[test]
+    fn set_breakpoint_inserts_and_removes() {
+        let mut emulator = Emulator::default();
+
+        assert_eq!(
+            set_breakpoint(&mut emulator, "0,204,2", true),
+            Ok("OK".into())
+        );
+        assert!(emulator.debugger.breakpoints.contains_key(&0x204));
+
+        assert_eq!(
+            set_breakpoint(&mut emulator, "0,204,2", false),
+            Ok("OK".into())
+        );
+        assert!(emulator.debugger.breakpoints.is_empty());
+    }
+
+    #[test]
+    fn set_breakpoint_adds_watchpoints() {
+        let mut emulator = Emulator::default();
+
+        assert_eq!(
+            set_breakpoint(&mut emulator, "2,300,4", true),
+            Ok("OK".into())
+        );
+        assert!(emulator.debugger.watchpoints.contains(&Watchpoint {
+            start: 0x300,
+            end: 0x303,
+            kind: WatchKind::Write,
+        }));
+
+        assert_eq!(
+            set_breakpoint(&mut emulator, "2,300,4", false),
+            Ok("OK".into())
+        );
+        assert!(emulator.debugger.watchpoints.is_empty());
+    }
+
+    #[test]
+    fn set_breakpoint_rejects_bad_arguments() {
+        let mut emulator = Emulator::default();
+
+        assert_eq!(
+            set_breakpoint(&mut emulator, "2,ffffffffffffffff,2", true),
+            Err("E01".to_string())
+        );
+        assert_eq!(
+            set_breakpoint(&mut emulator, "0,zz", true),
+            Err("E01".into())
+        );
+        assert_eq!(
+            set_breakpoint(&mut emulator, "9,200,1", true),
+            Ok(String::new())
+        );
+    }
+}
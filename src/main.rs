@@ -1,12 +1,19 @@
 // hide console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+pub mod capture;
 pub mod chip_8;
+pub mod cli;
 pub mod emulator_app;
+pub mod gdb_stub;
+pub mod saved_settings;
+pub mod scripting;
 pub mod ui;
 
+use crate::cli::Args;
 use crate::emulator_app::EmulatorApp;
+use clap::Parser;
 
 fn main() {
-    EmulatorApp::run();
+    EmulatorApp::run(Args::parse());
 }
@@ -0,0 +1,116 @@
+use crate::chip_8::config::{Config, EmulationSettings};
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::keypad::Keybindings;
+use crate::chip_8::sound::{Beeper, Sound};
+use anyhow::{anyhow, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Settings kept across sessions, saved as TOML in the config directory of the platform
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedSettings {
+    /// Emulation settings used for all ROMs without settings of their own
+    pub emulation: EmulationSettings,
+
+    pub volume: f32,
+    pub keybindings: Keybindings,
+
+    /// Everything else, including the last selected ROM and the per-ROM overrides
+    pub config: Config,
+}
+
+impl Default for SavedSettings {
+    fn default() -> Self {
+        Self {
+            emulation: EmulationSettings::default(),
+            volume: Beeper::DEFAULT_VOLUME,
+            keybindings: Keybindings::default(),
+            config: Config::default(),
+        }
+    }
+}
+
+impl SavedSettings {
+    /// Take the current settings of the emulator
+    pub fn new(emulator: &Emulator) -> Self {
+        let config = emulator.config.clone();
+
+        // Settings for the loaded ROM only are already part of the per-ROM overrides
+        let emulation = config
+            .global_emulation
+            .unwrap_or_else(|| emulator.emulation_settings());
+
+        Self {
+            emulation,
+            volume: emulator.beeper.get_volume(),
+            keybindings: emulator.keypad.keybindings(),
+            config,
+        }
+    }
+
+    pub fn filepath() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "Chip 8 Emulator")
+            .map(|dirs| dirs.config_dir().join("settings.toml"))
+    }
+
+    /// Load the saved settings. Returns the default settings if none were saved yet. Invalid
+    /// settings are moved to `settings.toml.bak` before returning the error
+    pub fn load() -> Result<Self> {
+        let Some(filepath) = Self::filepath().filter(|filepath| filepath.exists()) else {
+            return Ok(Self::default());
+        };
+
+        let text = fs::read_to_string(&filepath).map_err(|error| {
+            anyhow!(error).context(format!("Error reading settings at {}", filepath.display()))
+        })?;
+
+        toml::from_str(&text).map_err(|error| {
+            // Keep the invalid settings, so saving the defaults doesn't lose them
+            let backup = filepath.with_extension("toml.bak");
+            let context = match fs::rename(&filepath, &backup) {
+                Ok(()) => format!(
+                    "Invalid settings at {}, moved to {}",
+                    filepath.display(),
+                    backup.display()
+                ),
+                Err(_) => format!("Invalid settings at {}", filepath.display()),
+            };
+
+            anyhow!(error).context(context)
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let filepath = Self::filepath()
+            .ok_or_else(|| anyhow!("No config directory found to save settings"))?;
+
+        if let Some(folder) = filepath.parent() {
+            fs::create_dir_all(folder).map_err(|error| {
+                anyhow!(error).context(format!("Error creating folder at {}", folder.display()))
+            })?;
+        }
+
+        fs::write(&filepath, toml::to_string(self)?).map_err(|error| {
+            anyhow!(error).context(format!("Error saving settings at {}", filepath.display()))
+        })
+    }
+
+    /// Use the settings in the emulator. Returns an error if the beeper sample can't be loaded,
+    /// after applying all other settings
+    pub fn apply(&self, emulator: &mut Emulator) -> Result<()> {
+        emulator.config = Config {
+            global_emulation: None,
+            ..self.config.clone()
+        };
+
+        emulator.apply_emulation_settings(self.emulation);
+        emulator.beeper.set_volume(self.volume);
+        emulator.keypad.set_keybindings(&self.keybindings);
+        emulator.beeper.set_sound(Sound::load(&self.config.sound)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+use crate::capture::screenshot;
+use crate::chip_8::emulator::Emulator;
+use crate::emulator_app::Event;
+use anyhow::{anyhow, Result};
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, NativeCallContext, AST};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Operations a script may run at once, like when loaded or in a callback, before it is stopped.
+/// Keeps a script stuck in a loop from freezing the emulator
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+/// Frames `advance_frames` emulates at most at once, which is a minute of emulated time
+const MAX_ADVANCE_FRAMES: i64 = 60 * 60;
+
+/// A Rhai script, which can read and change the machine state, press keys, advance frames and
+/// take screenshots, to automate testing or play games.
+///
+/// The script runs once when loaded, and can register callbacks which run while emulating:
+/// `on_frame_end(callback)` after every frame, `on_instruction(address, callback)` before the
+/// instruction at the address and `on_draw(callback)` after every instruction drawing a sprite or
+/// clearing the display, with the address of the instruction as parameter.
+///
+/// The machine state is available as `v(x)`, `i()`, `pc()`, `dt()`, `st()`, `frame()`,
+/// `peek(address)` and `pixel(x, y)`, and changed with `set_v(x, value)`, `set_i(value)`,
+/// `set_pc(value)`, `set_dt(value)`, `set_st(value)` and `poke(address, value)`.
+/// `press_key(key)` and `release_key(key)` hold down Chip 8 keys, `advance_frames(count)` emulates
+/// up to `MAX_ADVANCE_FRAMES` frames right away and `screenshot()` saves a screenshot, returning
+/// its path.
+/// Scripts are stopped with an error once they run more than `MAX_OPERATIONS` operations at once
+pub struct Script {
+    pub filepath: PathBuf,
+
+    engine: Engine,
+    ast: AST,
+    state: Rc<RefCell<ScriptState>>,
+}
+
+#[derive(Default)]
+struct ScriptState {
+    frame_end: Vec<FnPtr>,
+    instructions: BTreeMap<usize, Vec<FnPtr>>,
+    draw: Vec<FnPtr>,
+
+    /// Bitmask of the keys held down by the script.
+    /// The least significant bit corresponds to key 0
+    held_keys: u16,
+
+    /// Keys the script released, which are shown to the ROM as released for a frame
+    released_keys: u16,
+
+    /// Event which stopped the emulation while the script advanced frames
+    stopped: Option<Event>,
+}
+
+/// Why a frame emulated by a script stopped early
+enum FrameError {
+    Emulation(Event),
+    Script(Box<EvalAltResult>),
+}
+
+impl Script {
+    /// Load the script and run it, which usually registers callbacks
+    pub fn load(filepath: &Path, emulator: &Rc<RefCell<Emulator>>) -> Result<Self> {
+        let source = fs::read_to_string(filepath).map_err(|error| {
+            anyhow!(error).context(format!("Error reading script at {}", filepath.display()))
+        })?;
+
+        let state = Rc::new(RefCell::new(ScriptState::default()));
+        let engine = Self::create_engine(emulator, &state);
+
+        let ast = engine
+            .compile(&source)
+            .map_err(|error| anyhow!("Error in script {}: {}", filepath.display(), error))?;
+
+        let script = Self {
+            filepath: filepath.to_path_buf(),
+            engine,
+            ast,
+            state,
+        };
+
+        if let Err(error) = script.engine.run_ast(&script.ast) {
+            return Err(script.error(error));
+        }
+
+        Ok(script)
+    }
+
+    /// Emulate a frame, running the callbacks of the script
+    pub fn run_frame(&mut self, emulator: &Rc<RefCell<Emulator>>) -> Result<(), Event> {
+        let result = run_frame(emulator, &self.state, &mut |callback, args| {
+            callback
+                .call::<Dynamic>(&self.engine, &self.ast, args)
+                .map(|_| ())
+        });
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(FrameError::Emulation(event)) => Err(event),
+            Err(FrameError::Script(error)) => Err(match self.state.borrow_mut().stopped.take() {
+                Some(event) => event,
+                None => Event::ScriptError(self.error(error)),
+            }),
+        }
+    }
+
+    fn error(&self, error: Box<EvalAltResult>) -> anyhow::Error {
+        anyhow!("Error in script {}: {}", self.filepath.display(), error)
+    }
+
+    fn create_engine(emulator: &Rc<RefCell<Emulator>>, state: &Rc<RefCell<ScriptState>>) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        macro_rules! register {
+            ($name:literal, |$emulator:ident $(, $arg:ident)*| $body:expr) => {{
+                let emulator = emulator.clone();
+                engine.register_fn($name, move |$($arg: i64),*| {
+                    #[allow(unused_mut)]
+                    let mut $emulator = emulator.borrow_mut();
+                    $body
+                });
+            }};
+        }
+
+        register!("v", |emulator, x| {
+            Ok::<_, Box<EvalAltResult>>(emulator.v_regs[register(x)?] as i64)
+        });
+        register!("i", |emulator| emulator.i_reg as i64);
+        register!("pc", |emulator| emulator.pc as i64);
+        register!("dt", |emulator| emulator.delay_timer as i64);
+        register!("st", |emulator| emulator.sound_timer as i64);
+        register!("frame", |emulator| emulator.frames as i64);
+
+        register!("set_v", |emulator, x, value| {
+            emulator.v_regs[register(x)?] = value as u8;
+            Ok::<_, Box<EvalAltResult>>(())
+        });
+        register!("set_i", |emulator, value| emulator.i_reg =
+            value as usize & 0xFFFF);
+        register!("set_pc", |emulator, value| {
+            emulator.pc = program_counter(&emulator, value)?;
+            Ok::<_, Box<EvalAltResult>>(())
+        });
+        register!("set_dt", |emulator, value| emulator.delay_timer =
+            value as u8);
+        register!("set_st", |emulator, value| emulator.sound_timer =
+            value as u8);
+
+        register!("peek", |emulator, address| {
+            let address = memory_address(&emulator, address)?;
+            Ok::<_, Box<EvalAltResult>>(emulator.memory.data[address] as i64)
+        });
+
+        register!("poke", |emulator, address, value| {
+            let address = memory_address(&emulator, address)?;
+            emulator.memory.data[address] = value as u8;
+            Ok::<_, Box<EvalAltResult>>(())
+        });
+
+        register!("pixel", |emulator, x, y| {
+            let display = &emulator.display;
+            let (x, y) = (x as usize, y as usize);
+
+            if x >= display.resolution.width() || y >= display.resolution.height() {
+                return Err::<bool, Box<EvalAltResult>>(
+                    format!("Pixel {}, {} is outside the display", x, y).into(),
+                );
+            }
+
+            Ok(display.planes.iter().any(|plane| plane.pixels[y][x]))
+        });
+
+        register!("screenshot", |emulator| {
+            screenshot::save_screenshot(&emulator)
+                .map(|filepath| filepath.display().to_string())
+                .map_err(|error| Box::<EvalAltResult>::from(format!("{:#}", error)))
+        });
+
+        let shared = state.clone();
+        engine.register_fn("press_key", move |key: i64| -> ScriptResult<()> {
+            shared.borrow_mut().held_keys |= key_mask(key)?;
+            Ok(())
+        });
+
+        let shared = state.clone();
+        engine.register_fn("release_key", move |key: i64| -> ScriptResult<()> {
+            let state = &mut *shared.borrow_mut();
+            let mask = key_mask(key)?;
+
+            if state.held_keys & mask != 0 {
+                state.held_keys &= !mask;
+                state.released_keys |= mask;
+            }
+
+            Ok(())
+        });
+
+        let shared = state.clone();
+        engine.register_fn("on_frame_end", move |callback: FnPtr| {
+            shared.borrow_mut().frame_end.push(callback);
+        });
+
+        let shared = state.clone();
+        engine.register_fn("on_instruction", move |address: i64, callback: FnPtr| {
+            let state = &mut *shared.borrow_mut();
+            state
+                .instructions
+                .entry(address as usize)
+                .or_default()
+                .push(callback);
+        });
+
+        let shared = state.clone();
+        engine.register_fn("on_draw", move |callback: FnPtr| {
+            shared.borrow_mut().draw.push(callback);
+        });
+
+        let (emulator, shared) = (emulator.clone(), state.clone());
+        engine.register_fn(
+            "advance_frames",
+            move |context: NativeCallContext, frames: i64| -> ScriptResult<()> {
+                if !emulator.borrow().rom_loaded {
+                    return Err("No ROM is loaded".into());
+                }
+
+                if !(0..=MAX_ADVANCE_FRAMES).contains(&frames) {
+                    return Err(format!(
+                        "Can only advance 0 to {} frames at once",
+                        MAX_ADVANCE_FRAMES
+                    )
+                    .into());
+                }
+
+                for _ in 0..frames {
+                    let result = run_frame(&emulator, &shared, &mut |callback, args| {
+                        callback
+                            .call_within_context::<Dynamic>(&context, args)
+                            .map(|_| ())
+                    });
+
+                    match result {
+                        Ok(()) => {}
+                        Err(FrameError::Script(error)) => return Err(error),
+                        Err(FrameError::Emulation(event)) => {
+                            // Stop the script, and let the app handle the event
+                            shared.borrow_mut().stopped = Some(event);
+                            return Err("Emulation stopped".into());
+                        }
+                    }
+                }
+
+                Ok(())
+            },
+        );
+
+        engine
+    }
+}
+
+/// Emulate a frame like `Emulator::run_frame`, calling the callbacks of the script in between
+fn run_frame(
+    emulator: &Rc<RefCell<Emulator>>,
+    state: &Rc<RefCell<ScriptState>>,
+    call: &mut dyn FnMut(&FnPtr, Vec<Dynamic>) -> ScriptResult<()>,
+) -> Result<(), FrameError> {
+    let instructions = {
+        let emulator = &mut *emulator.borrow_mut();
+        apply_keys(emulator, &mut state.borrow_mut());
+
+        emulator.start_frame().map_err(FrameError::Emulation)?;
+        emulator.instructions_due()
+    };
+
+    for _ in 0..instructions {
+        let (pc, opcode) = {
+            let emulator = emulator.borrow();
+            let data = &emulator.memory.data;
+            let byte = |address: usize| data.get(address).copied().unwrap_or(0) as u16;
+
+            (emulator.pc, byte(emulator.pc) << 8 | byte(emulator.pc + 1))
+        };
+
+        // Clone the callbacks, since they may register further callbacks
+        let callbacks = state.borrow().instructions.get(&pc).cloned();
+        for callback in callbacks.iter().flatten() {
+            call(callback, Vec::new()).map_err(FrameError::Script)?;
+        }
+
+        emulator
+            .borrow_mut()
+            .execute_instruction()
+            .map_err(FrameError::Emulation)?;
+
+        if opcode & 0xF000 == 0xD000 || opcode == 0x00E0 {
+            let callbacks = state.borrow().draw.clone();
+            for callback in &callbacks {
+                call(callback, vec![Dynamic::from(pc as i64)]).map_err(FrameError::Script)?;
+            }
+        }
+    }
+
+    emulator
+        .borrow_mut()
+        .end_frame()
+        .map_err(FrameError::Emulation)?;
+
+    let callbacks = state.borrow().frame_end.clone();
+    for callback in &callbacks {
+        call(callback, Vec::new()).map_err(FrameError::Script)?;
+    }
+
+    Ok(())
+}
+
+/// Add the keys held down by the script to the keys pressed on the keyboard
+fn apply_keys(emulator: &mut Emulator, state: &mut ScriptState) {
+    let (pressed, released) = emulator.keypad.state_masks();
+    let pressed = pressed | state.held_keys;
+    let released = (released | state.released_keys) & !pressed;
+
+    emulator.keypad.set_state_masks(pressed, released);
+    state.released_keys = 0;
+}
+
+fn register(x: i64) -> ScriptResult<usize> {
+    if (0..16).contains(&x) {
+        Ok(x as usize)
+    } else {
+        Err(format!("There is no register V{}", x).into())
+    }
+}
+
+fn key_mask(key: i64) -> ScriptResult<u16> {
+    if (0..16).contains(&key) {
+        Ok(1 << key)
+    } else {
+        Err(format!("There is no key {}", key).into())
+    }
+}
+
+fn memory_address(emulator: &Emulator, address: i64) -> ScriptResult<usize> {
+    usize::try_from(address)
+        .ok()
+        .filter(|address| *address < emulator.memory.size)
+        .ok_or_else(|| format!("Memory address {} is out of bounds", address).into())
+}
+
+/// Validate a program counter, which must leave room for a whole instruction in memory
+fn program_counter(emulator: &Emulator, address: i64) -> ScriptResult<usize> {
+    usize::try_from(address)
+        .ok()
+        .filter(|address| *address < emulator.memory.size.saturating_sub(1))
+        .ok_or_else(|| format!("Program counter {} is outside memory", address).into())
+}
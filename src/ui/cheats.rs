@@ -0,0 +1,225 @@
+use crate::chip_8::cheats::{Cheat, CheatSearch, SearchFilter};
+use crate::chip_8::emulator::Emulator;
+use crate::ui::debugger::parse_address;
+use eframe::egui::{DragValue, Grid, ScrollArea, TextEdit, Ui};
+
+/// How many candidates of a search are listed
+const MAX_LISTED_CANDIDATES: usize = 100;
+
+/// Searches memory for the variables of a game, and edits the cheats of the loaded ROM
+#[derive(Default)]
+pub struct Cheats {
+    search: Option<CheatSearch>,
+    search_value: u8,
+
+    /// ROM the search was started for
+    search_rom: String,
+
+    address: String,
+    value: u8,
+    error: Option<String>,
+}
+
+impl Cheats {
+    pub fn draw_cheats(&mut self, ui: &mut Ui, emulator: &mut Emulator) {
+        let Some(rom_name) = emulator.config.rom_name() else {
+            ui.label("Select a ROM to use cheats");
+            return;
+        };
+
+        self.draw_search(ui, emulator, &rom_name);
+        ui.add_space(10f32);
+
+        self.draw_cheat_list(ui, emulator, &rom_name);
+    }
+
+    fn draw_search(&mut self, ui: &mut Ui, emulator: &mut Emulator, rom_name: &str) {
+        // The candidates of a search don't apply to another ROM or memory size
+        if self.search_rom != rom_name
+            || self
+                .search
+                .as_ref()
+                .is_some_and(|search| !search.matches(&emulator.memory))
+        {
+            self.search = None;
+        }
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("New Search")
+                .on_hover_text("Take a snapshot of memory, with all addresses as candidates.")
+                .clicked()
+            {
+                self.search = Some(CheatSearch::start(&emulator.memory));
+                self.search_rom = rom_name.to_string();
+            }
+
+            if let Some(search) = &self.search {
+                ui.label(format!("{} candidates", search.candidates.len()));
+            }
+        });
+
+        let Some(search) = &mut self.search else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            for filter in SearchFilter::COMPARISONS {
+                if ui
+                    .button(filter.to_string())
+                    .on_hover_text(format!(
+                        "Keep the candidates whose value {} since the last search.",
+                        filter.to_string().to_lowercase()
+                    ))
+                    .clicked()
+                {
+                    search.filter(&emulator.memory, filter);
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Equals")
+                .on_hover_text("Keep the candidates with this value.")
+                .clicked()
+            {
+                search.filter(&emulator.memory, SearchFilter::Equals(self.search_value));
+            }
+
+            ui.add(DragValue::new(&mut self.search_value));
+        });
+
+        let mut added = None;
+
+        ScrollArea::vertical()
+            .id_salt("cheat_candidates")
+            .max_height(150f32)
+            .show(ui, |ui| {
+                Grid::new("cheat_candidates_grid")
+                    .striped(true)
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        ui.strong("Address");
+                        ui.strong("Previous");
+                        ui.strong("Current");
+                        ui.strong("");
+                        ui.end_row();
+
+                        for &address in search.candidates.iter().take(MAX_LISTED_CANDIDATES) {
+                            let Some(&current) = emulator.memory.data.get(address) else {
+                                continue;
+                            };
+
+                            ui.monospace(format!("{:#05X}", address));
+                            ui.label(search.previous_value(address).unwrap_or(0).to_string());
+                            ui.label(current.to_string());
+
+                            if ui.button("Add Cheat").clicked() {
+                                added = Some(Cheat::new(address, current));
+                            }
+
+                            ui.end_row();
+                        }
+                    });
+
+                if search.candidates.len() > MAX_LISTED_CANDIDATES {
+                    ui.label(format!(
+                        "and {} more",
+                        search.candidates.len() - MAX_LISTED_CANDIDATES
+                    ));
+                }
+            });
+
+        if let Some(cheat) = added {
+            Self::add_cheat(emulator, rom_name, cheat);
+        }
+    }
+
+    fn draw_cheat_list(&mut self, ui: &mut Ui, emulator: &mut Emulator, rom_name: &str) {
+        ui.label(format!("Cheats for {}", rom_name));
+
+        let mut poked = None;
+        let mut removed = None;
+
+        if let Some(cheats) = emulator.config.cheats.get_mut(rom_name) {
+            Grid::new("cheat_list_grid")
+                .striped(true)
+                .num_columns(6)
+                .show(ui, |ui| {
+                    for (index, cheat) in cheats.iter_mut().enumerate() {
+                        ui.checkbox(&mut cheat.enabled, "")
+                            .on_hover_text("Freeze the address at the value every frame");
+                        ui.add(
+                            TextEdit::singleline(&mut cheat.description)
+                                .hint_text("Description")
+                                .desired_width(120f32),
+                        );
+                        ui.monospace(format!("{:#05X}", cheat.address));
+                        ui.add(DragValue::new(&mut cheat.value).hexadecimal(2, false, true));
+
+                        if ui
+                            .button("Poke")
+                            .on_hover_text("Write the value once")
+                            .clicked()
+                        {
+                            poked = Some(cheat.clone());
+                        }
+
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+
+                        ui.end_row();
+                    }
+                });
+
+            if let Some(index) = removed {
+                cheats.remove(index);
+
+                if cheats.is_empty() {
+                    emulator.config.cheats.remove(rom_name);
+                }
+            }
+        }
+
+        if let Some(cheat) = poked {
+            cheat.apply(&mut emulator.memory);
+        }
+
+        ui.add_space(5f32);
+
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.address)
+                    .hint_text("Address")
+                    .desired_width(60f32),
+            );
+            ui.add(DragValue::new(&mut self.value).hexadecimal(2, false, true));
+
+            if ui.button("Add").clicked() {
+                match parse_address(&self.address) {
+                    Ok(address) => {
+                        Self::add_cheat(emulator, rom_name, Cheat::new(address, self.value));
+                        self.address.clear();
+                        self.error = None;
+                    }
+                    Err(error) => self.error = Some(error),
+                }
+            }
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
+
+    fn add_cheat(emulator: &mut Emulator, rom_name: &str, cheat: Cheat) {
+        emulator
+            .config
+            .cheats
+            .entry(rom_name.to_string())
+            .or_default()
+            .push(cheat);
+    }
+}
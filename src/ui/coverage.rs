@@ -0,0 +1,137 @@
+use crate::chip_8::coverage::{ByteUse, Coverage};
+use crate::chip_8::emulator::INSTRUCTIONS_START;
+use crate::chip_8::memory::Memory;
+use eframe::egui::{vec2, Color32, Rect, ScrollArea, Sense, Ui, Vec2};
+
+const BYTES_PER_ROW: usize = 64;
+
+/// Size of a byte in the coverage map in points
+const CELL_SIZE: f32 = 6f32;
+
+/// Shows a colour-coded map of how each byte of the loaded ROM was used
+#[derive(Default)]
+pub struct CoverageView {
+    error: Option<String>,
+}
+
+impl CoverageView {
+    pub fn draw_coverage(&mut self, ui: &mut Ui, coverage: &mut Option<Coverage>, memory: &Memory) {
+        ui.horizontal(|ui| {
+            if coverage.is_none() {
+                if ui
+                    .button("Start Recording")
+                    .on_hover_text("Record which bytes are executed, read and written from now on.")
+                    .clicked()
+                {
+                    *coverage = Some(Coverage::new(memory.size));
+                }
+            } else {
+                if ui.button("Stop Recording").clicked() {
+                    *coverage = None;
+                }
+
+                if ui.button("Restart").clicked() {
+                    *coverage = Some(Coverage::new(memory.size));
+                }
+            }
+
+            if let Some(coverage) = coverage {
+                if ui.button("Export Report...").clicked() {
+                    self.export(coverage, memory.rom_size);
+                }
+            }
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        let Some(coverage) = coverage else {
+            return;
+        };
+
+        if memory.rom_size == 0 {
+            ui.label("No ROM loaded");
+            return;
+        }
+
+        let (start, end) = (INSTRUCTIONS_START, INSTRUCTIONS_START + memory.rom_size);
+
+        ui.horizontal_wrapped(|ui| {
+            for (byte_use, count) in ByteUse::ALL.iter().zip(coverage.summary(start, end)) {
+                ui.colored_label(color(*byte_use), "■");
+                ui.label(format!(
+                    "{} {:.1}%",
+                    byte_use,
+                    count as f64 / memory.rom_size as f64 * 100.0
+                ));
+                ui.add_space(5f32);
+            }
+        });
+
+        ScrollArea::vertical()
+            .id_salt("coverage_map")
+            .max_height(200f32)
+            .show(ui, |ui| {
+                let rows = memory.rom_size.div_ceil(BYTES_PER_ROW);
+                let (rect, response) = ui.allocate_exact_size(
+                    vec2(BYTES_PER_ROW as f32, rows as f32) * CELL_SIZE,
+                    Sense::hover(),
+                );
+                let painter = ui.painter_at(rect);
+
+                for address in start..end {
+                    let offset = address - start;
+                    let position = rect.min
+                        + vec2(
+                            (offset % BYTES_PER_ROW) as f32,
+                            (offset / BYTES_PER_ROW) as f32,
+                        ) * CELL_SIZE;
+
+                    painter.rect_filled(
+                        Rect::from_min_size(position, Vec2::splat(CELL_SIZE - 1f32)),
+                        0f32,
+                        color(coverage.byte_use(address)),
+                    );
+                }
+
+                if let Some(pointer) = response.hover_pos() {
+                    let cell = (pointer - rect.min) / CELL_SIZE;
+                    let address = start + cell.y as usize * BYTES_PER_ROW + cell.x as usize;
+
+                    if address < end {
+                        response.on_hover_text(format!(
+                            "{:#05X}: {:#04X}, {}",
+                            address,
+                            memory.data[address],
+                            coverage.byte_use(address)
+                        ));
+                    }
+                }
+            });
+    }
+
+    fn export(&mut self, coverage: &Coverage, rom_size: usize) {
+        let filepath = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("coverage.csv")
+            .save_file();
+
+        if let Some(filepath) = filepath {
+            self.error = coverage
+                .export_report(&filepath, rom_size)
+                .err()
+                .map(|error| format!("{:#}", error));
+        }
+    }
+}
+
+fn color(byte_use: ByteUse) -> Color32 {
+    match byte_use {
+        ByteUse::Unused => Color32::from_gray(60),
+        ByteUse::Code => Color32::from_rgb(80, 200, 80),
+        ByteUse::Data => Color32::from_rgb(80, 140, 230),
+        ByteUse::Written => Color32::from_rgb(230, 150, 50),
+        ByteUse::SelfModifyingCode => Color32::from_rgb(220, 80, 220),
+    }
+}
@@ -0,0 +1,417 @@
+use crate::chip_8::debugger::{Breakpoint, WatchKind, Watchpoint};
+use crate::chip_8::disassembler;
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::expression::{Expression, Template};
+use crate::emulator_app::Event;
+use crate::ui::coverage::CoverageView;
+use crate::ui::profiler::ProfilerView;
+use eframe::egui::{ComboBox, Grid, RichText, ScrollArea, TextEdit, Ui};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// How many instructions are disassembled, starting at the program counter
+const DISASSEMBLED_INSTRUCTIONS: usize = 12;
+
+/// Shows the machine state while the emulation is paused, and manages breakpoints and watchpoints
+pub struct DebuggerView {
+    emulator: Rc<RefCell<Emulator>>,
+
+    breakpoint_address: String,
+    breakpoint_condition: String,
+    breakpoint_hit_target: String,
+    breakpoint_message: String,
+
+    watch_start: String,
+    watch_end: String,
+    watch_kind: WatchKind,
+
+    profiler: ProfilerView,
+    coverage: CoverageView,
+
+    error: Option<String>,
+}
+
+impl DebuggerView {
+    pub fn new(emulator: Rc<RefCell<Emulator>>) -> Self {
+        Self {
+            emulator,
+            breakpoint_address: String::new(),
+            breakpoint_condition: String::new(),
+            breakpoint_hit_target: String::new(),
+            breakpoint_message: String::new(),
+            watch_start: String::new(),
+            watch_end: String::new(),
+            watch_kind: WatchKind::Write,
+            profiler: ProfilerView::default(),
+            coverage: CoverageView::default(),
+            error: None,
+        }
+    }
+
+    pub fn draw_debugger(&mut self, ui: &mut Ui) -> Option<Event> {
+        let event = self.draw_controls(ui);
+        ui.separator();
+
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| self.draw_registers(ui));
+            ui.add_space(20f32);
+            ui.vertical(|ui| self.draw_disassembly(ui));
+        });
+
+        ui.separator();
+        self.draw_breakpoints(ui);
+        ui.add_space(5f32);
+        self.draw_watchpoints(ui);
+        ui.add_space(5f32);
+        self.draw_trace_log(ui);
+        ui.add_space(5f32);
+        self.draw_profiler(ui);
+        ui.add_space(5f32);
+        self.draw_coverage(ui);
+
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        event
+    }
+
+    fn draw_controls(&mut self, ui: &mut Ui) -> Option<Event> {
+        let emulator = &mut *self.emulator.borrow_mut();
+
+        if let Some(reason) = &emulator.debugger.break_reason {
+            ui.label(RichText::new(reason).strong());
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Continue").clicked() {
+                return Some(Event::StartEmulation);
+            }
+
+            if ui
+                .button("Step")
+                .on_hover_text("Execute the next instruction.")
+                .clicked()
+            {
+                match emulator.step() {
+                    // The debugger is open already
+                    Err(Event::Break) | Ok(()) => {}
+                    Err(event) => return Some(event),
+                }
+            }
+
+            if ui.button("Close").clicked() {
+                return Some(Event::PauseEmulation);
+            }
+
+            None
+        })
+        .inner
+    }
+
+    fn draw_registers(&self, ui: &mut Ui) {
+        let emulator = self.emulator.borrow();
+
+        Grid::new("debugger_registers")
+            .striped(true)
+            .num_columns(4)
+            .show(ui, |ui| {
+                for (name, value) in [
+                    ("PC", format!("{:#05X}", emulator.pc)),
+                    ("I", format!("{:#05X}", emulator.i_reg)),
+                    ("SP", emulator.stack.len().to_string()),
+                    ("DT", emulator.delay_timer.to_string()),
+                    ("ST", emulator.sound_timer.to_string()),
+                ] {
+                    ui.strong(name);
+                    ui.monospace(value);
+                    ui.end_row();
+                }
+
+                for row in 0..8 {
+                    for reg in [row, row + 8] {
+                        ui.strong(format!("V{:X}", reg));
+                        ui.monospace(format!("{:#04X}", emulator.v_regs[reg]));
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn draw_disassembly(&mut self, ui: &mut Ui) {
+        let emulator = &mut *self.emulator.borrow_mut();
+        let mut address = emulator.pc;
+
+        ui.label("Click an instruction to toggle a breakpoint at it.");
+
+        for _ in 0..DISASSEMBLED_INSTRUCTIONS {
+            if address >= emulator.memory.size {
+                break;
+            }
+
+            let (text, size) = disassembler::disassemble(&emulator.memory.data, address);
+            let marker = match (
+                address == emulator.pc,
+                emulator.debugger.breakpoints.contains_key(&address),
+            ) {
+                (true, true) => "▶●",
+                (true, false) => "▶ ",
+                (false, true) => " ●",
+                (false, false) => "  ",
+            };
+
+            let line = format!("{} {:#05X}  {}", marker, address, text);
+
+            if ui
+                .selectable_label(false, RichText::new(line).monospace())
+                .clicked()
+            {
+                emulator.debugger.toggle_breakpoint(address);
+            }
+
+            address += size;
+        }
+    }
+
+    fn draw_breakpoints(&mut self, ui: &mut Ui) {
+        let emulator = self.emulator.clone();
+        let debugger = &mut emulator.borrow_mut().debugger;
+
+        ui.collapsing("Breakpoints", |ui| {
+            let mut removed = None;
+
+            for (address, breakpoint) in &mut debugger.breakpoints {
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("{:#05X}", address));
+                    ui.label(breakpoint.to_string());
+
+                    if ui.small_button("Reset Hits").clicked() {
+                        breakpoint.hits = 0;
+                    }
+
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some(*address);
+                    }
+                });
+            }
+
+            if let Some(address) = removed {
+                debugger.breakpoints.remove(&address);
+            }
+
+            Grid::new("breakpoint_form").num_columns(2).show(ui, |ui| {
+                ui.label("Address");
+                ui.add(TextEdit::singleline(&mut self.breakpoint_address).desired_width(80f32));
+                ui.end_row();
+
+                ui.label("Condition")
+                    .on_hover_text("Only break when it is true, like v3 == 0x10 && i > 0x400");
+                ui.text_edit_singleline(&mut self.breakpoint_condition);
+                ui.end_row();
+
+                ui.label("Hit Count")
+                    .on_hover_text("Only break once the condition was true this many times.");
+                ui.add(TextEdit::singleline(&mut self.breakpoint_hit_target).desired_width(80f32));
+                ui.end_row();
+
+                ui.label("Log Message").on_hover_text(
+                    "Log the message instead of breaking. Expressions in braces are replaced by \
+                    their value, like {mem[0x300]}, or by their value in hex, like {i:x}.",
+                );
+                ui.text_edit_singleline(&mut self.breakpoint_message);
+                ui.end_row();
+            });
+
+            if ui.button("Add").clicked() {
+                match self.parse_breakpoint() {
+                    Ok((address, breakpoint)) => {
+                        debugger.breakpoints.insert(address, breakpoint);
+                        self.breakpoint_address.clear();
+                        self.breakpoint_condition.clear();
+                        self.breakpoint_hit_target.clear();
+                        self.breakpoint_message.clear();
+                        self.error = None;
+                    }
+                    Err(error) => self.error = Some(error),
+                }
+            }
+        })
+        .header_response
+        .on_hover_text(
+            "Pause the emulation before the instruction at an address is executed, or log a \
+            message as a tracepoint.",
+        );
+    }
+
+    fn draw_watchpoints(&mut self, ui: &mut Ui) {
+        let emulator = self.emulator.clone();
+        let debugger = &mut emulator.borrow_mut().debugger;
+
+        ui.collapsing("Watchpoints", |ui| {
+            let mut removed = None;
+
+            for (index, watchpoint) in debugger.watchpoints.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.monospace(watchpoint.to_string());
+
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+
+            if let Some(index) = removed {
+                debugger.watchpoints.remove(index);
+            }
+
+            ui.horizontal(|ui| {
+                ComboBox::from_id_salt("watch_kind")
+                    .selected_text(self.watch_kind.to_string())
+                    .show_ui(ui, |ui| {
+                        for kind in WatchKind::ALL {
+                            ui.selectable_value(&mut self.watch_kind, kind, kind.to_string());
+                        }
+                    });
+
+                ui.add(
+                    TextEdit::singleline(&mut self.watch_start)
+                        .hint_text("Start")
+                        .desired_width(80f32),
+                );
+                ui.add(
+                    TextEdit::singleline(&mut self.watch_end)
+                        .hint_text("End")
+                        .desired_width(80f32),
+                )
+                .on_hover_text("Last address of the range. Leave empty to watch a single address.");
+
+                if ui.button("Add").clicked() {
+                    match self.parse_watchpoint() {
+                        Ok(watchpoint) => {
+                            debugger.watchpoints.push(watchpoint);
+                            self.watch_start.clear();
+                            self.watch_end.clear();
+                            self.error = None;
+                        }
+                        Err(error) => self.error = Some(error),
+                    }
+                }
+            });
+        })
+        .header_response
+        .on_hover_text(
+            "Pause the emulation after an instruction reads or writes memory in a range.",
+        );
+    }
+
+    fn draw_trace_log(&mut self, ui: &mut Ui) {
+        let debugger = &mut self.emulator.borrow_mut().debugger;
+
+        ui.collapsing("Trace Log", |ui| {
+            ScrollArea::vertical()
+                .id_salt("trace_log")
+                .max_height(150f32)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for message in &debugger.trace_log {
+                        ui.monospace(message);
+                    }
+                });
+
+            if ui.button("Clear").clicked() {
+                debugger.trace_log.clear();
+            }
+        })
+        .header_response
+        .on_hover_text("Messages logged by tracepoints.");
+    }
+
+    fn draw_profiler(&mut self, ui: &mut Ui) {
+        let emulator = &mut *self.emulator.borrow_mut();
+        let instructions_per_frame = emulator.config.instructions_per_frame;
+
+        ui.collapsing("Profiler", |ui| {
+            self.profiler
+                .draw_profiler(ui, &mut emulator.profiler, instructions_per_frame);
+        })
+        .header_response
+        .on_hover_text("Find the hot spots of the ROM, and how many instructions it needs.");
+    }
+
+    fn draw_coverage(&mut self, ui: &mut Ui) {
+        let emulator = &mut *self.emulator.borrow_mut();
+
+        ui.collapsing("Coverage", |ui| {
+            self.coverage
+                .draw_coverage(ui, &mut emulator.coverage, &emulator.memory);
+        })
+        .header_response
+        .on_hover_text("Find the parts of the ROM which were never reached, and its data.");
+    }
+
+    fn parse_breakpoint(&self) -> Result<(usize, Breakpoint), String> {
+        let address = parse_address(&self.breakpoint_address)?;
+
+        let condition = optional(&self.breakpoint_condition)
+            .map(Expression::parse)
+            .transpose()
+            .map_err(|error| error.to_string())?;
+
+        let hit_target = optional(&self.breakpoint_hit_target)
+            .map(|text| {
+                text.parse()
+                    .map_err(|_| format!("Invalid hit count: '{}'", text))
+            })
+            .transpose()?;
+
+        let log_message = optional(&self.breakpoint_message)
+            .map(Template::parse)
+            .transpose()
+            .map_err(|error| error.to_string())?;
+
+        Ok((
+            address,
+            Breakpoint {
+                condition,
+                hit_target,
+                hits: 0,
+                log_message,
+            },
+        ))
+    }
+
+    fn parse_watchpoint(&self) -> Result<Watchpoint, String> {
+        let start = parse_address(&self.watch_start)?;
+        let end = if self.watch_end.trim().is_empty() {
+            start
+        } else {
+            parse_address(&self.watch_end)?
+        };
+
+        if end < start {
+            return Err("The end of the range must not be before its start".to_string());
+        }
+
+        Ok(Watchpoint {
+            start,
+            end,
+            kind: self.watch_kind,
+        })
+    }
+}
+
+/// Trim the text of an optional field, which is empty if not given
+fn optional(text: &str) -> Option<&str> {
+    Some(text.trim()).filter(|text| !text.is_empty())
+}
+
+/// Parse a hexadecimal address, with or without a `0x` prefix
+pub fn parse_address(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+
+    usize::from_str_radix(digits, 16).map_err(|_| format!("Invalid address: '{}'", text))
+}
@@ -0,0 +1,129 @@
+use crate::chip_8::config::AntiFlickerMode;
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::palette::Palette;
+use eframe::egui::Color32;
+use std::collections::VecDeque;
+
+/// Applies the configured anti flicker post-processing to the display before it is drawn
+#[derive(Default)]
+pub struct FrameFilter {
+    /// The most recent frames, newest last. Used for frame blending
+    history: VecDeque<Vec<(bool, bool)>>,
+
+    /// Brightness of every pixel on both planes, from 0 to 1. Used for phosphor decay
+    intensities: Vec<[f32; 2]>,
+
+    /// The frame shown while waiting for the program to redraw erased sprites
+    held_frame: Vec<(bool, bool)>,
+
+    /// The emulated frame which was last processed
+    last_frame: u64,
+}
+
+impl FrameFilter {
+    /// Get the colors of all pixels of the display, row by row
+    pub fn process(&mut self, emulator: &Emulator) -> Vec<Color32> {
+        let pixels: Vec<(bool, bool)> = emulator
+            .display
+            .zip_planes()
+            .into_iter()
+            .flatten()
+            .collect();
+        let palette = emulator.config.active_palette();
+        let anti_flicker = emulator.config.anti_flicker;
+
+        // Start over when the resolution changed or the emulator was reset
+        if pixels.len() != self.held_frame.len() || emulator.frames < self.last_frame {
+            *self = Self {
+                held_frame: pixels.clone(),
+                intensities: vec![[0f32; 2]; pixels.len()],
+                ..Default::default()
+            };
+        }
+
+        let elapsed_frames = (emulator.frames - self.last_frame) as i32;
+        self.last_frame = emulator.frames;
+
+        match anti_flicker.mode {
+            AntiFlickerMode::Off => pixels
+                .into_iter()
+                .map(|pixel| palette.color(pixel))
+                .collect(),
+
+            AntiFlickerMode::FrameBlending => {
+                if elapsed_frames > 0 || self.history.is_empty() {
+                    self.history.push_back(pixels);
+                }
+
+                while self.history.len() > anti_flicker.blend_frames.max(1) {
+                    self.history.pop_front();
+                }
+
+                let weight = 1f32 / self.history.len() as f32;
+                let mut intensities = vec![[0f32; 2]; self.held_frame.len()];
+
+                for frame in &self.history {
+                    for (intensity, (plane1, plane2)) in intensities.iter_mut().zip(frame) {
+                        intensity[0] += *plane1 as u8 as f32 * weight;
+                        intensity[1] += *plane2 as u8 as f32 * weight;
+                    }
+                }
+
+                intensities
+                    .into_iter()
+                    .map(|intensity| mix(palette, intensity))
+                    .collect()
+            }
+
+            AntiFlickerMode::PhosphorDecay => {
+                let decay = anti_flicker.persistence.powi(elapsed_frames);
+
+                for (intensity, (plane1, plane2)) in self.intensities.iter_mut().zip(pixels) {
+                    for (plane_intensity, lit) in intensity.iter_mut().zip([plane1, plane2]) {
+                        *plane_intensity = if lit { 1f32 } else { *plane_intensity * decay };
+                    }
+                }
+
+                self.intensities
+                    .iter()
+                    .map(|intensity| mix(palette, *intensity))
+                    .collect()
+            }
+
+            AntiFlickerMode::WaitForRedraw => {
+                if !emulator.display.erased {
+                    self.held_frame = pixels;
+                }
+
+                self.held_frame
+                    .iter()
+                    .map(|pixel| palette.color(*pixel))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Mix the palette colors, weighted by how bright the pixel is on each plane
+fn mix(palette: &Palette, [plane1, plane2]: [f32; 2]) -> Color32 {
+    let weights = [
+        (1f32 - plane1) * (1f32 - plane2),
+        plane1 * (1f32 - plane2),
+        (1f32 - plane1) * plane2,
+        plane1 * plane2,
+    ];
+
+    let mut rgb = [0f32; 3];
+
+    for (color, weight) in palette.colors.iter().zip(weights) {
+        rgb[0] += color.r() as f32 * weight;
+        rgb[1] += color.g() as f32 * weight;
+        rgb[2] += color.b() as f32 * weight;
+    }
+
+    Color32::from_rgb(
+        rgb[0].round() as u8,
+        rgb[1].round() as u8,
+        rgb[2].round() as u8,
+    )
+}
@@ -0,0 +1,45 @@
+use crate::chip_8::emulator::Emulator;
+use crate::chip_8::keypad::{KeyState, HEX_KEYS};
+use eframe::egui;
+use eframe::egui::{Context, RichText, Vec2};
+
+const BUTTON_SIZE: f32 = 56.0;
+
+/// Draw the Chip 8 keypad as clickable buttons in the COSMAC VIP layout.
+/// Held buttons press the key, and keys pressed on the keyboard are highlighted.
+pub fn draw_keypad(ctx: &Context, emulator: &mut Emulator) {
+    let keypad = &mut emulator.keypad;
+    let mut touched_keys = 0;
+
+    egui::Window::new("Keypad")
+        .resizable(false)
+        .default_pos(egui::Pos2::new(ctx.screen_rect().width() - 280.0, 60.0))
+        .show(ctx, |ui| {
+            ui.style_mut().spacing.item_spacing = Vec2::splat(4.0);
+
+            egui::Grid::new("keypad_grid").show(ui, |ui| {
+                for (index, hex_key) in HEX_KEYS.iter().enumerate() {
+                    let key = &keypad.keys[*hex_key as usize];
+
+                    let button =
+                        egui::Button::new(RichText::new(format!("{:X}", hex_key)).size(24.0))
+                            .min_size(Vec2::splat(BUTTON_SIZE))
+                            .selected(key.state == KeyState::Pressed);
+
+                    let response = ui
+                        .add(button)
+                        .on_hover_text(format!("Keyboard key: {}", key.egui_key.name()));
+
+                    if response.is_pointer_button_down_on() {
+                        touched_keys |= 1 << hex_key;
+                    }
+
+                    if index % 4 == 3 {
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+
+    keypad.touched_keys = touched_keys;
+}
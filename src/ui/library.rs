@@ -0,0 +1,299 @@
+use crate::chip_8::archive;
+use crate::chip_8::config::{LibraryConfig, Platform};
+use crate::chip_8::rom_database;
+use chrono::{DateTime, Local};
+use eframe::egui::{Grid, ScrollArea, Spinner, TextEdit, Ui};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// How many of the most recently played ROMs are listed
+const RECENT_ROMS: usize = 10;
+
+/// Lists the ROMs of the library folders, to select one of them
+#[derive(Default)]
+pub struct Library {
+    entries: Vec<LibraryEntry>,
+
+    /// The folders the entries were scanned from
+    scanned_folders: Option<Vec<PathBuf>>,
+
+    /// Receives the entries of the scan running in the background
+    scan: Option<Receiver<Vec<LibraryEntry>>>,
+
+    search: String,
+    favourites_only: bool,
+}
+
+struct LibraryEntry {
+    filepath: PathBuf,
+    name: String,
+
+    /// Size in bytes
+    size: u64,
+
+    /// Title and platform, if the ROM is in the ROM database
+    title: Option<String>,
+    platform: Option<Platform>,
+}
+
+impl Library {
+    /// Draw the library. Returns the ROM the user selected
+    pub fn draw_library(&mut self, ui: &mut Ui, config: &mut LibraryConfig) -> Option<PathBuf> {
+        if self.scanned_folders.as_ref() != Some(&config.folders) {
+            self.scan(&config.folders);
+        }
+
+        self.poll_scan(ui);
+
+        let mut selected = None;
+        let mut toggled_favourite = None;
+
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.search).hint_text("Search"));
+            ui.checkbox(&mut self.favourites_only, "Favourites only");
+
+            if self.scan.is_some() {
+                ui.add(Spinner::new());
+                ui.label("Scanning...");
+            }
+        });
+
+        ui.add_space(5f32);
+
+        ScrollArea::vertical()
+            .id_salt("rom_library")
+            .max_height(220f32)
+            .show(ui, |ui| {
+                Grid::new("rom_library_grid")
+                    .striped(true)
+                    .num_columns(5)
+                    .show(ui, |ui| {
+                        ui.strong("");
+                        ui.strong("ROM");
+                        ui.strong("Size");
+                        ui.strong("Platform");
+                        ui.strong("Last Played");
+                        ui.end_row();
+
+                        for entry in self.entries.iter().filter(|entry| {
+                            entry.matches(&self.search)
+                                && (!self.favourites_only || config.is_favourite(&entry.filepath))
+                        }) {
+                            let favourite = config.is_favourite(&entry.filepath);
+
+                            if ui
+                                .selectable_label(favourite, if favourite { "★" } else { "☆" })
+                                .on_hover_text("Toggle favourite")
+                                .clicked()
+                            {
+                                toggled_favourite = Some(entry.filepath.clone());
+                            }
+
+                            let label = ui.selectable_label(false, &entry.name);
+                            let label = match &entry.title {
+                                Some(title) => label.on_hover_text(title),
+                                None => label,
+                            };
+
+                            if label.clicked() {
+                                selected = Some(entry.filepath.clone());
+                            }
+
+                            ui.label(format!("{} B", entry.size));
+                            ui.label(
+                                entry
+                                    .platform
+                                    .map_or("Unknown".to_string(), |platform| platform.to_string()),
+                            );
+                            ui.label(format_last_played(config.last_played(&entry.filepath)));
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if let Some(filepath) = toggled_favourite {
+            config.toggle_favourite(&filepath);
+        }
+
+        ui.add_space(5f32);
+
+        ui.horizontal(|ui| {
+            if ui.button("Rescan").clicked() {
+                self.scan(&config.folders);
+            }
+
+            if ui.button("Add Folder...").clicked() {
+                if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                    config.folders.push(folder);
+                }
+            }
+        });
+
+        ui.collapsing("Library Folders", |ui| {
+            let mut removed = None;
+
+            for (index, folder) in config.folders.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(folder.display().to_string());
+
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+
+            if let Some(index) = removed {
+                config.folders.remove(index);
+            }
+        });
+
+        ui.collapsing("Recent ROMs", |ui| {
+            for rom in config.played.iter().take(RECENT_ROMS) {
+                let name = rom
+                    .filepath
+                    .file_name()
+                    .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+
+                if ui
+                    .selectable_label(false, name)
+                    .on_hover_text(format!(
+                        "{}\nLast played: {}",
+                        rom.filepath.display(),
+                        format_last_played(Some(rom.last_played))
+                    ))
+                    .clicked()
+                {
+                    selected = Some(rom.filepath.clone());
+                }
+            }
+        });
+
+        selected
+    }
+
+    /// Scan the folders on another thread, since every ROM is read and hashed. The entries of the
+    /// previous scan are listed until it finishes
+    fn scan(&mut self, folders: &[PathBuf]) {
+        let (tx, rx) = mpsc::channel();
+        let scan_folders = folders.to_vec();
+
+        thread::spawn(move || {
+            let mut entries = Vec::new();
+
+            for folder in &scan_folders {
+                scan_folder(folder, &mut entries);
+            }
+
+            entries.sort_by_key(|entry| entry.name.to_lowercase());
+
+            // The scan was replaced by a newer one, if nothing receives the entries anymore
+            let _ = tx.send(entries);
+        });
+
+        self.scan = Some(rx);
+        self.scanned_folders = Some(folders.to_vec());
+    }
+
+    fn poll_scan(&mut self, ui: &Ui) {
+        let Some(scan) = &self.scan else {
+            return;
+        };
+
+        match scan.try_recv() {
+            Ok(entries) => {
+                self.entries = entries;
+                self.scan = None;
+            }
+            Err(TryRecvError::Empty) => ui.ctx().request_repaint_after(Duration::from_millis(100)),
+            Err(TryRecvError::Disconnected) => self.scan = None,
+        }
+    }
+}
+
+impl LibraryEntry {
+    fn new(filepath: PathBuf, data: &[u8]) -> Self {
+        let info = rom_database::lookup(&rom_database::hash(data));
+
+        Self {
+            name: filepath
+                .file_name()
+                .map_or(String::new(), |name| name.to_string_lossy().into_owned()),
+            size: data.len() as u64,
+            title: info.as_ref().map(|info| info.title.clone()),
+            platform: info.and_then(|info| info.platform),
+            filepath,
+        }
+    }
+
+    fn matches(&self, search: &str) -> bool {
+        let search = search.to_lowercase();
+
+        self.name.to_lowercase().contains(&search)
+            || self
+                .title
+                .as_ref()
+                .is_some_and(|title| title.to_lowercase().contains(&search))
+    }
+}
+
+/// Add all ROMs in the folder, its subfolders and archives. Files which can't be read are skipped
+fn scan_folder(folder: &Path, entries: &mut Vec<LibraryEntry>) {
+    let Ok(dir_entries) = fs::read_dir(folder) else {
+        return;
+    };
+
+    for dir_entry in dir_entries.flatten() {
+        let filepath = dir_entry.path();
+
+        // Symbolic links aren't followed, since they may form loops
+        if dir_entry
+            .file_type()
+            .is_ok_and(|file_type| file_type.is_dir())
+        {
+            scan_folder(&filepath, entries);
+            continue;
+        }
+
+        if archive::is_archive(&filepath) {
+            for entry in archive::list_roms(&filepath).unwrap_or_default() {
+                if let Ok(data) = archive::read_rom(&filepath, Some(&entry)) {
+                    entries.push(LibraryEntry::new(
+                        archive::rom_path(&filepath, &entry),
+                        &data,
+                    ));
+                }
+            }
+
+            continue;
+        }
+
+        if !archive::is_rom(&filepath.to_string_lossy()) {
+            continue;
+        }
+
+        // Files too large for any platform aren't read, since they can't be ROMs
+        if dir_entry
+            .metadata()
+            .is_ok_and(|metadata| metadata.len() > archive::max_rom_size() as u64)
+        {
+            continue;
+        }
+
+        if let Ok(data) = fs::read(&filepath) {
+            entries.push(LibraryEntry::new(filepath, &data));
+        }
+    }
+}
+
+fn format_last_played(timestamp: Option<i64>) -> String {
+    timestamp
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+        .map_or("Never".to_string(), |time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+}
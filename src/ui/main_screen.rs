@@ -1,14 +1,17 @@
+use crate::chip_8::config::{Scaling, ScalingMode};
 use crate::chip_8::emulator::Emulator;
 use crate::emulator_app::{Event, FrameData};
+use crate::ui::frame_filter::FrameFilter;
 use crate::ui::{MENU_BAR_OFFSET, TEXTURE_OPTIONS};
 use eframe::egui;
-use eframe::egui::{Ui, Vec2};
+use eframe::egui::{Rect, Ui, Vec2};
 use std::cell::RefCell;
 use std::rc::Rc;
 
 pub struct MainScreen {
     emulator: Rc<RefCell<Emulator>>,
     frame_data: Rc<RefCell<FrameData>>,
+    frame_filter: FrameFilter,
 }
 
 impl MainScreen {
@@ -16,15 +19,21 @@ impl MainScreen {
         Self {
             emulator,
             frame_data,
+            frame_filter: FrameFilter::default(),
         }
     }
 
-    pub fn draw_main_screen(&self, ui: &mut Ui, window_size: Vec2, paused: bool) -> Option<Event> {
+    pub fn draw_main_screen(
+        &mut self,
+        ui: &mut Ui,
+        window_size: Vec2,
+        paused: bool,
+    ) -> Option<Event> {
         self.draw_display(ui, window_size);
         self.draw_menu_bar(ui, window_size, paused)
     }
 
-    fn draw_display(&self, ui: &Ui, window_size: Vec2) {
+    fn draw_display(&mut self, ui: &Ui, window_size: Vec2) {
         let emulator = self.emulator.borrow();
 
         let width = emulator.display.resolution.width();
@@ -32,17 +41,8 @@ impl MainScreen {
 
         let mut image_data: Vec<u8> = Vec::with_capacity(width * height * 4);
 
-        for row in emulator.display.zip_planes() {
-            for pixel in row {
-                let color = match pixel {
-                    (false, false) => egui::Color32::BLACK,
-                    (true, false) => egui::Color32::WHITE,
-                    (false, true) => egui::Color32::LIGHT_GREEN,
-                    (true, true) => egui::Color32::DARK_GREEN,
-                };
-
-                image_data.extend_from_slice(&[color.r(), color.g(), color.b(), color.a()]);
-            }
+        for color in self.frame_filter.process(&emulator) {
+            image_data.extend_from_slice(&[color.r(), color.g(), color.b(), color.a()]);
         }
 
         let color_image = egui::ColorImage::from_rgba_unmultiplied([width, height], &image_data);
@@ -50,13 +50,73 @@ impl MainScreen {
             .ctx()
             .load_texture("display_texture", color_image, TEXTURE_OPTIONS);
 
-        egui::Image::from(&texture_handle).paint_at(
-            ui,
-            egui::Rect::from_two_pos(
-                egui::Pos2::ZERO,
-                egui::Pos2::new(window_size.x, window_size.y - MENU_BAR_OFFSET),
-            ),
+        let display_area = egui::Rect::from_two_pos(
+            egui::Pos2::ZERO,
+            egui::Pos2::new(window_size.x, window_size.y - MENU_BAR_OFFSET),
         );
+
+        let scaling = emulator.config.scaling;
+        let display_rect = Self::scale_display(display_area, width, height, scaling.mode);
+
+        ui.painter()
+            .rect_filled(display_area, 0f32, egui::Color32::BLACK);
+        egui::Image::from(&texture_handle).paint_at(ui, display_rect);
+
+        Self::draw_overlays(ui, display_rect, width, height, scaling);
+    }
+
+    /// Get the area the display is drawn in, according to the scaling mode
+    fn scale_display(area: Rect, width: usize, height: usize, mode: ScalingMode) -> Rect {
+        let size = Vec2::new(width as f32, height as f32);
+        let scale = (area.width() / size.x).min(area.height() / size.y);
+
+        let scale = match mode {
+            ScalingMode::Stretch => return area,
+            ScalingMode::KeepAspect => scale,
+            ScalingMode::Integer => scale.floor().max(1f32),
+        };
+
+        // Round the position, so the pixels line up with the screen pixels
+        Rect::from_min_size((area.center() - size * scale / 2f32).round(), size * scale)
+    }
+
+    fn draw_overlays(ui: &Ui, rect: Rect, width: usize, height: usize, scaling: Scaling) {
+        let painter = ui.painter();
+        let pixel_size = Vec2::new(rect.width() / width as f32, rect.height() / height as f32);
+
+        if scaling.scanlines {
+            let color = egui::Color32::from_black_alpha(96);
+
+            for row in 0..height {
+                let top = rect.top() + (row as f32 + 0.5) * pixel_size.y;
+
+                painter.rect_filled(
+                    Rect::from_x_y_ranges(rect.x_range(), top..=top + pixel_size.y / 2f32),
+                    0f32,
+                    color,
+                );
+            }
+        }
+
+        if scaling.pixel_grid {
+            let stroke = egui::Stroke::new(1f32, egui::Color32::from_black_alpha(128));
+
+            for column in 1..width {
+                painter.vline(
+                    rect.left() + column as f32 * pixel_size.x,
+                    rect.y_range(),
+                    stroke,
+                );
+            }
+
+            for row in 1..height {
+                painter.hline(
+                    rect.x_range(),
+                    rect.top() + row as f32 * pixel_size.y,
+                    stroke,
+                );
+            }
+        }
     }
 
     fn draw_menu_bar(&self, ui: &mut Ui, window_size: Vec2, paused: bool) -> Option<Event> {
@@ -69,8 +129,8 @@ impl MainScreen {
         if ui
             .put(
                 egui::Rect::from_two_pos(
-                    egui::Pos2::new(window_center - 50f32, bar_height),
-                    egui::Pos2::new(window_center + 50f32, bar_top_height),
+                    egui::Pos2::new(window_center - 105f32, bar_height),
+                    egui::Pos2::new(window_center - 5f32, bar_top_height),
                 ),
                 egui::Button::new(egui::RichText::new("Open Settings")),
             )
@@ -83,8 +143,8 @@ impl MainScreen {
         if ui
             .put(
                 egui::Rect::from_two_pos(
-                    egui::Pos2::new(window_center - 200f32, bar_height),
-                    egui::Pos2::new(window_center - 100f32, bar_top_height),
+                    egui::Pos2::new(window_center - 215f32, bar_height),
+                    egui::Pos2::new(window_center - 115f32, bar_top_height),
                 ),
                 egui::Button::new("Pause/Resume"),
             )
@@ -100,8 +160,21 @@ impl MainScreen {
         if ui
             .put(
                 egui::Rect::from_two_pos(
-                    egui::Pos2::new(window_center + 100f32, bar_height),
-                    egui::Pos2::new(window_center + 200f32, bar_top_height),
+                    egui::Pos2::new(window_center + 5f32, bar_height),
+                    egui::Pos2::new(window_center + 105f32, bar_top_height),
+                ),
+                egui::Button::new("Screenshot"),
+            )
+            .clicked()
+        {
+            return Some(Event::TakeScreenshot);
+        }
+
+        if ui
+            .put(
+                egui::Rect::from_two_pos(
+                    egui::Pos2::new(window_center + 115f32, bar_height),
+                    egui::Pos2::new(window_center + 215f32, bar_top_height),
                 ),
                 egui::Button::new("Exit Emulator"),
             )
@@ -116,9 +189,9 @@ impl MainScreen {
                 egui::Pos2::new(300f32, bar_top_height),
             ),
             egui::Label::new(if paused {
-                "Emulation Paused"
+                "Emulation Paused".to_string()
             } else {
-                "Emulation Running"
+                format!("Emulation Running ({})", self.frame_data.borrow().speed)
             }),
         );
 
@@ -1,15 +1,25 @@
+mod cheats;
+mod coverage;
+mod debugger;
+mod frame_filter;
+mod keypad_overlay;
+mod library;
 mod main_screen;
+mod profiler;
 mod settings;
 
 use crate::chip_8::emulator::Emulator;
+use crate::chip_8::error::EmulationError;
 use crate::emulator_app::{Event, FrameData, FONT_SIZE};
+use crate::ui::debugger::DebuggerView;
 use crate::ui::main_screen::MainScreen;
 use anyhow::Error;
 use eframe::egui;
-use eframe::egui::{Context, FontId};
+use eframe::egui::{Context, FontId, Id};
 use settings::Settings;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 pub const MENU_BAR_OFFSET: f32 = 30.0;
 const TEXTURE_OPTIONS: egui::TextureOptions = egui::TextureOptions {
@@ -19,23 +29,51 @@ const TEXTURE_OPTIONS: egui::TextureOptions = egui::TextureOptions {
     mipmap_mode: None,
 };
 
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(3);
+
 pub struct Screen {
+    emulator: Rc<RefCell<Emulator>>,
     main_screen: MainScreen,
     settings: Settings,
+    debugger: DebuggerView,
+
+    /// Message shown on top of the display, together with the time it was shown at
+    notification: Option<(String, Instant)>,
 }
 
 impl Screen {
     pub fn new(emulator: Rc<RefCell<Emulator>>, frame_data: Rc<RefCell<FrameData>>) -> Self {
         let main_screen = MainScreen::new(emulator.clone(), frame_data);
-        let settings = Settings::new(emulator);
+        let settings = Settings::new(emulator.clone());
+        let debugger = DebuggerView::new(emulator.clone());
 
         Self {
+            emulator,
             main_screen,
             settings,
+            debugger,
+            notification: None,
         }
     }
 
+    /// Show a message on top of the display for a few seconds
+    pub fn notify(&mut self, message: String) {
+        self.notification = Some((message, Instant::now()));
+    }
+
     pub fn draw_main_screen(&mut self, ctx: &Context, paused: bool) -> Option<Event> {
+        self.draw_notification(ctx);
+
+        {
+            let emulator = &mut *self.emulator.borrow_mut();
+
+            if emulator.config.show_keypad {
+                keypad_overlay::draw_keypad(ctx, emulator);
+            } else {
+                emulator.keypad.touched_keys = 0;
+            }
+        }
+
         egui::CentralPanel::default()
             .show(ctx, |ui| {
                 ui.style_mut().override_font_id = None;
@@ -56,6 +94,22 @@ impl Screen {
             .inner
     }
 
+    fn draw_notification(&mut self, ctx: &Context) {
+        if let Some((message, shown_at)) = &self.notification {
+            if shown_at.elapsed() > NOTIFICATION_DURATION {
+                self.notification = None;
+                return;
+            }
+
+            egui::Area::new(Id::new("notification"))
+                .fixed_pos(egui::Pos2::new(10f32, 10f32))
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| ui.label(message.as_str()));
+                });
+        }
+    }
+
     pub fn draw_settings(&mut self, ctx: &Context) -> Option<Event> {
         egui::Window::new("Settings")
             .collapsible(false)
@@ -70,6 +124,16 @@ impl Screen {
             .unwrap()
     }
 
+    pub fn draw_debugger(&mut self, ctx: &Context) -> Option<Event> {
+        egui::Window::new("Debugger")
+            .collapsible(false)
+            .default_pos(egui::Pos2::new(30.0, 30.0))
+            .show(ctx, |ui| self.debugger.draw_debugger(ui))
+            .unwrap()
+            .inner
+            .unwrap()
+    }
+
     pub fn draw_error(&mut self, ctx: &Context, error: &Error) -> Option<Event> {
         egui::Window::new("Error trying to run ROM")
             .collapsible(false)
@@ -88,6 +152,23 @@ impl Screen {
                     ui.separator();
                     ui.add_space(10f32);
 
+                    // Errors of an instruction can be inspected in the debugger, which shows the
+                    // faulting instruction
+                    let faulting_pc = error
+                        .downcast_ref::<EmulationError>()
+                        .and_then(EmulationError::pc);
+
+                    if let Some(pc) = faulting_pc {
+                        if ui.button("Open Debugger").clicked() {
+                            self.emulator.borrow_mut().debugger.break_reason = Some(format!(
+                                "Error at {:#05X}: {}",
+                                pc,
+                                error.to_string().lines().next().unwrap_or_default()
+                            ));
+                            return Some(Event::OpenDebugger);
+                        }
+                    }
+
                     if ui.button("Exit Emulator").clicked() {
                         Some(Event::Exit)
                     } else {
@@ -0,0 +1,298 @@
+use crate::chip_8::disassembler;
+use crate::chip_8::profiler::Profiler;
+use eframe::egui::{Grid, ScrollArea, Ui};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+
+/// How many rows of a table are listed
+const MAX_ROWS: usize = 200;
+
+/// Shows the statistics of the profiler as sortable tables
+pub struct ProfilerView {
+    table: Table,
+    sort_column: usize,
+    ascending: bool,
+    error: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Table {
+    Addresses,
+    Classes,
+    Subroutines,
+}
+
+/// A cell of a table, with the value it is sorted by
+struct Cell {
+    text: String,
+    key: f64,
+}
+
+impl Default for ProfilerView {
+    fn default() -> Self {
+        Self {
+            table: Table::Addresses,
+            // Sort by count, most executed first
+            sort_column: 2,
+            ascending: false,
+            error: None,
+        }
+    }
+}
+
+impl ProfilerView {
+    pub fn draw_profiler(
+        &mut self,
+        ui: &mut Ui,
+        profiler: &mut Option<Profiler>,
+        instructions_per_frame: u32,
+    ) {
+        ui.horizontal(|ui| {
+            if profiler.is_none() {
+                if ui
+                    .button("Start Profiling")
+                    .on_hover_text("Count the executed instructions from now on.")
+                    .clicked()
+                {
+                    *profiler = Some(Profiler::default());
+                }
+            } else {
+                if ui.button("Stop Profiling").clicked() {
+                    *profiler = None;
+                }
+
+                if ui.button("Restart").clicked() {
+                    *profiler = Some(Profiler::default());
+                }
+            }
+
+            if let Some(profiler) = profiler {
+                if ui.button("Export CSV...").clicked() {
+                    self.export(profiler);
+                }
+            }
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        let Some(profiler) = profiler else {
+            return;
+        };
+
+        ui.label(format!(
+            "{} instructions in {} frames",
+            profiler.instructions,
+            profiler.frames()
+        ));
+        ui.label(format!(
+            "Instructions per frame needed before waiting: {:.1} on average, {} in 95% of frames, \
+             {} at most (currently {})",
+            profiler.average_frame_instructions(),
+            profiler.frame_percentile(0.95),
+            profiler
+                .frame_instructions
+                .keys()
+                .last()
+                .copied()
+                .unwrap_or(0),
+            instructions_per_frame
+        ))
+        .on_hover_text(
+            "Instructions executed each frame before the ROM reads a running delay timer, waits \
+             for a key press or jumps to itself.",
+        );
+
+        ui.horizontal(|ui| {
+            for table in [Table::Addresses, Table::Classes, Table::Subroutines] {
+                if ui
+                    .selectable_label(self.table == table, table.to_string())
+                    .clicked()
+                    && self.table != table
+                {
+                    self.table = table;
+                    self.sort_column = 0;
+                    self.ascending = true;
+                }
+            }
+        });
+
+        self.draw_table(ui, profiler);
+    }
+
+    fn draw_table(&mut self, ui: &mut Ui, profiler: &Profiler) {
+        let (columns, mut rows) = self.table.rows(profiler);
+
+        rows.sort_by(|a, b| {
+            let ordering = a[self.sort_column]
+                .key
+                .partial_cmp(&b[self.sort_column].key)
+                .unwrap_or(Ordering::Equal);
+
+            if self.ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+
+        ScrollArea::vertical()
+            .id_salt("profiler_table")
+            .max_height(250f32)
+            .show(ui, |ui| {
+                Grid::new("profiler_table_grid")
+                    .striped(true)
+                    .num_columns(columns.len())
+                    .show(ui, |ui| {
+                        for (index, column) in columns.iter().enumerate() {
+                            let sorted = self.sort_column == index;
+                            let arrow = match (sorted, self.ascending) {
+                                (false, _) => "",
+                                (true, true) => " ⏶",
+                                (true, false) => " ⏷",
+                            };
+
+                            if ui
+                                .selectable_label(sorted, format!("{}{}", column, arrow))
+                                .clicked()
+                            {
+                                if sorted {
+                                    self.ascending = !self.ascending;
+                                } else {
+                                    self.sort_column = index;
+                                    self.ascending = false;
+                                }
+                            }
+                        }
+                        ui.end_row();
+
+                        for row in rows.iter().take(MAX_ROWS) {
+                            for cell in row {
+                                ui.monospace(&cell.text);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                if rows.len() > MAX_ROWS {
+                    ui.label(format!("and {} more", rows.len() - MAX_ROWS));
+                }
+            });
+    }
+
+    fn export(&mut self, profiler: &Profiler) {
+        let filepath = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("profile.csv")
+            .save_file();
+
+        if let Some(filepath) = filepath {
+            self.error = profiler
+                .export_csv(&filepath)
+                .err()
+                .map(|error| format!("{:#}", error));
+        }
+    }
+}
+
+impl Table {
+    /// The column names and rows of the table
+    fn rows(self, profiler: &Profiler) -> (&'static [&'static str], Vec<Vec<Cell>>) {
+        let total = profiler.instructions.max(1) as f64;
+        let percent = |count: u64| Cell {
+            text: format!("{:.2}%", count as f64 / total * 100.0),
+            key: count as f64,
+        };
+
+        match self {
+            Table::Addresses => (
+                &["Address", "Instruction", "Count", "Share"],
+                profiler
+                    .addresses
+                    .iter()
+                    .map(|(&address, stats)| {
+                        vec![
+                            Cell::address(address),
+                            Cell {
+                                text: disassembler::disassemble_opcode(stats.opcode),
+                                key: stats.opcode as f64,
+                            },
+                            Cell::number(stats.count),
+                            percent(stats.count),
+                        ]
+                    })
+                    .collect(),
+            ),
+            Table::Classes => {
+                let mut classes: Vec<_> = profiler.classes.iter().collect();
+                classes.sort();
+
+                (
+                    &["Opcode", "Count", "Share"],
+                    classes
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, (class, &count))| {
+                            vec![
+                                Cell {
+                                    text: class.to_string(),
+                                    key: index as f64,
+                                },
+                                Cell::number(count),
+                                percent(count),
+                            ]
+                        })
+                        .collect(),
+                )
+            }
+            Table::Subroutines => (
+                &["Address", "Calls", "Instructions", "Per Call", "Share"],
+                profiler
+                    .subroutines
+                    .iter()
+                    .map(|(&address, stats)| {
+                        let per_call = stats.instructions as f64 / stats.calls.max(1) as f64;
+
+                        vec![
+                            Cell::address(address),
+                            Cell::number(stats.calls),
+                            Cell::number(stats.instructions),
+                            Cell {
+                                text: format!("{:.1}", per_call),
+                                key: per_call,
+                            },
+                            percent(stats.instructions),
+                        ]
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Table::Addresses => write!(f, "Addresses"),
+            Table::Classes => write!(f, "Opcodes"),
+            Table::Subroutines => write!(f, "Subroutines"),
+        }
+    }
+}
+
+impl Cell {
+    fn address(address: usize) -> Self {
+        Self {
+            text: format!("{:#05X}", address),
+            key: address as f64,
+        }
+    }
+
+    fn number(value: u64) -> Self {
+        Self {
+            text: value.to_string(),
+            key: value as f64,
+        }
+    }
+}
@@ -1,22 +1,56 @@
+use crate::chip_8::archive;
+use crate::chip_8::config::{
+    AntiFlickerMode, AudioOutput, Platform, RecordingFormat, ScalingMode, Speed, Timing, Waveform,
+};
 use crate::chip_8::emulator::Emulator;
 use crate::chip_8::keypad::HEX_KEYS;
+use crate::chip_8::movie::{Movie, MovieMode, MovieSession};
+use crate::chip_8::palette::Palette;
+use crate::chip_8::sound::Sound;
 use crate::emulator_app::Event;
-use eframe::egui::{ComboBox, Context, Id, Slider, SliderClamping, Ui, Widget};
+use crate::ui::cheats::Cheats;
+use crate::ui::library::Library;
+use anyhow::Error;
+use eframe::egui::color_picker::{self, Alpha};
+use eframe::egui::{
+    ComboBox, Context, Id, Response, ScrollArea, Slider, SliderClamping, Ui, ViewportCommand,
+    Widget,
+};
 use egui_keybind::Keybind;
 use std::cell::RefCell;
+use std::fs;
 use std::path::PathBuf;
 use std::rc::Rc;
 
 pub struct Settings {
     emulator: Rc<RefCell<Emulator>>,
+    library: Library,
+    cheats: Cheats,
+
+    /// An archive holding several ROMs, with the ROMs to choose from
+    archive_picker: Option<(PathBuf, Vec<String>)>,
+
+    rom_error: Option<String>,
+    palette_error: Option<String>,
+    movie_error: Option<String>,
+    sound_error: Option<String>,
 }
 
 impl Settings {
     pub fn new(emulator: Rc<RefCell<Emulator>>) -> Self {
-        Self { emulator }
+        Self {
+            emulator,
+            library: Library::default(),
+            cheats: Cheats::default(),
+            archive_picker: None,
+            rom_error: None,
+            palette_error: None,
+            movie_error: None,
+            sound_error: None,
+        }
     }
 
-    pub fn draw_settings(&self, ui: &mut Ui) -> Option<Event> {
+    pub fn draw_settings(&mut self, ui: &mut Ui) -> Option<Event> {
         ui.vertical_centered(|ui| {
             if let Some(filepath) = &self.emulator.borrow().get_rom() {
                 ui.label(format!(
@@ -27,10 +61,11 @@ impl Settings {
                 ui.label("No ROM selected");
             }
 
+            self.draw_rom_info(ui);
             ui.add_space(5.0);
 
             self.collect_dropped_files(ui.ctx());
-            self.file_dialog(ui);
+            self.draw_rom_selection(ui);
             ui.add_space(5.0);
 
             if self.emulator.borrow().rom_loaded && ui.button("Reload ROM").clicked() {
@@ -53,7 +88,30 @@ impl Settings {
             self.draw_keybindings(ui);
             ui.add_space(10.0);
 
+            self.draw_palette_settings(ui);
+            ui.add_space(10.0);
+
+            self.draw_display_settings(ui);
+            ui.add_space(10.0);
+
+            self.draw_capture_settings(ui);
+            ui.add_space(10.0);
+
             self.draw_other_settings(ui);
+            ui.add_space(10.0);
+
+            self.draw_cheat_settings(ui);
+            ui.add_space(10.0);
+
+            if let Some(event) = Self::draw_script_settings(ui) {
+                return Some(event);
+            }
+            ui.add_space(10.0);
+
+            if let Some(event) = self.draw_movie_settings(ui) {
+                return Some(event);
+            }
+
             ui.add_space(15.0);
 
             if ui.button("Start/Resume Emulation").clicked() {
@@ -77,24 +135,105 @@ impl Settings {
         .inner
     }
 
-    fn collect_dropped_files(&self, ctx: &Context) {
-        ctx.input(|input| {
-            if !input.raw.dropped_files.is_empty() {
-                let filepath = input.raw.dropped_files[0].path.clone().unwrap();
-                self.select_rom(filepath);
-            }
-        })
+    fn collect_dropped_files(&mut self, ctx: &Context) {
+        let dropped = ctx.input(|input| {
+            input
+                .raw
+                .dropped_files
+                .first()
+                .and_then(|file| file.path.clone())
+        });
+
+        if let Some(filepath) = dropped {
+            self.select_rom(filepath);
+        }
     }
 
-    fn file_dialog(&self, ui: &mut Ui) {
+    fn draw_rom_selection(&mut self, ui: &mut Ui) {
+        let mut selected = None;
+
+        ui.collapsing("ROM Library", |ui| {
+            let config = &mut self.emulator.borrow_mut().config;
+            selected = self.library.draw_library(ui, &mut config.library);
+        })
+        .header_response
+        .on_hover_text("ROMs found in the library folders. Click a ROM to select it.");
+
         if ui.button("Select File...").clicked() {
-            if let Some(filepath) = rfd::FileDialog::new().pick_file() {
-                self.select_rom(filepath);
-            }
+            selected = rfd::FileDialog::new().pick_file();
+        }
+
+        if let Some(filepath) = selected {
+            self.select_rom(filepath);
+        }
+
+        self.draw_archive_picker(ui);
+
+        if let Some(error) = &self.rom_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
         }
     }
 
-    fn select_rom(&self, filepath: PathBuf) {
+    fn draw_archive_picker(&mut self, ui: &mut Ui) {
+        let Some((archive, roms)) = &self.archive_picker else {
+            return;
+        };
+
+        let mut selected = None;
+        let mut cancelled = false;
+
+        ui.add_space(5.0);
+        ui.group(|ui| {
+            ui.label(format!(
+                "{} holds several ROMs, choose one:",
+                archive.file_name().unwrap_or_default().to_string_lossy()
+            ));
+
+            ScrollArea::vertical()
+                .id_salt("archive_picker")
+                .max_height(150f32)
+                .show(ui, |ui| {
+                    for rom in roms {
+                        if ui.selectable_label(false, rom).clicked() {
+                            selected = Some(archive::rom_path(archive, rom));
+                        }
+                    }
+                });
+
+            cancelled = ui.button("Cancel").clicked();
+        });
+
+        if let Some(filepath) = selected {
+            self.archive_picker = None;
+            self.select_rom(filepath);
+        } else if cancelled {
+            self.archive_picker = None;
+        }
+    }
+
+    /// Select a ROM. Archives holding a single ROM select that ROM, archives holding several ROMs
+    /// let the user choose one of them first
+    fn select_rom(&mut self, filepath: PathBuf) {
+        self.rom_error = None;
+
+        if archive::is_archive(&filepath) {
+            match archive::list_roms(&filepath) {
+                Ok(roms) if roms.len() == 1 => {
+                    let filepath = archive::rom_path(&filepath, &roms[0]);
+                    self.select_rom(filepath);
+                }
+                Ok(roms) if roms.is_empty() => {
+                    self.rom_error = Some(format!("No ROMs found in {}", filepath.display()));
+                }
+                Ok(roms) => self.archive_picker = Some((filepath, roms)),
+                Err(error) => self.rom_error = Some(format!("{:#}", error)),
+            }
+
+            return;
+        }
+
+        self.archive_picker = None;
+
         let emulator = &mut *self.emulator.borrow_mut();
 
         if emulator.rom_loaded {
@@ -104,18 +243,121 @@ impl Settings {
         emulator.select_rom(filepath);
     }
 
+    fn draw_rom_info(&self, ui: &mut Ui) {
+        let emulator = self.emulator.borrow();
+
+        let Some(info) = &emulator.rom_info else {
+            return;
+        };
+
+        let mut title = info.title.clone();
+
+        if !info.authors.is_empty() {
+            title += &format!(" by {}", info.authors.join(", "));
+        }
+
+        if let Some(release) = &info.release {
+            title += &format!(" ({})", release);
+        }
+
+        ui.strong(title);
+
+        if let Some(description) = &info.description {
+            ui.small(description);
+        }
+    }
+
     fn draw_emulation_settings(&self, ui: &mut Ui) {
         let emulator = &mut *self.emulator.borrow_mut();
 
         ui.collapsing("Emulation Settings", |ui| {
+            ui.checkbox(&mut emulator.config.use_rom_database, "Use ROM Database")
+                .on_hover_text(
+                    "Automatically choose the platform, speed, colors and keys for known ROMs when they are loaded.",
+                );
+
+            let current_platform = Platform::ALL.into_iter().find(|platform| {
+                emulator.config.quirks == platform.quirks()
+                    && emulator.config.timing == Timing::InstructionsPerFrame
+                    && emulator.config.instructions_per_frame == platform.instructions_per_frame()
+                    && emulator.memory.size == platform.memory_size()
+            });
+
+            ComboBox::from_label("Platform")
+                .selected_text(current_platform.map_or("Custom".to_string(), |platform| {
+                    platform.to_string()
+                }))
+                .show_ui(ui, |ui| {
+                    for platform in Platform::ALL {
+                        let selected = current_platform == Some(platform);
+
+                        if ui.selectable_label(selected, platform.to_string()).clicked() {
+                            emulator.apply_platform(platform);
+                        }
+                    }
+                })
+                .response
+                .on_hover_text("Set the quirks, speed and memory size to those of a Chip 8 variant.");
+
+            ui.add_space(5f32);
+
             let memory = &mut emulator.memory;
+            let config = &mut emulator.config;
 
-            ui.add(
-                Slider::new(&mut emulator.config.instructions_per_frame, 0..=1000)
-                    .clamping(SliderClamping::Never)
-                    .text("Instructions per Frame"),
-            )
-            .on_hover_text("How many Instructions are executed every frame. The target FPS is 60.");
+            ComboBox::from_label("Timing")
+                .selected_text(match config.timing {
+                    Timing::InstructionsPerFrame => "Instructions per Frame",
+                    Timing::InstructionsPerSecond => "Instructions per Second",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut config.timing,
+                        Timing::InstructionsPerFrame,
+                        "Instructions per Frame",
+                    );
+                    ui.selectable_value(
+                        &mut config.timing,
+                        Timing::InstructionsPerSecond,
+                        "Instructions per Second",
+                    );
+                })
+                .response
+                .on_hover_text("Choose whether the instruction rate is set per frame or per second.");
+
+            ui.add_space(5f32);
+
+            match config.timing {
+                Timing::InstructionsPerFrame => {
+                    ui.add(
+                        Slider::new(&mut config.instructions_per_frame, 0..=1000)
+                            .clamping(SliderClamping::Never)
+                            .text("Instructions per Frame"),
+                    )
+                    .on_hover_text(
+                        "How many Instructions are executed every frame. The target FPS is 60.",
+                    );
+                }
+
+                Timing::InstructionsPerSecond => {
+                    ui.add(
+                        Slider::new(&mut config.instructions_per_second, 0..=60000)
+                            .clamping(SliderClamping::Never)
+                            .text("Instructions per Second"),
+                    )
+                    .on_hover_text(
+                        "How many Instructions are executed every second. They are spread evenly across the 60 frames of a second.",
+                    );
+                }
+            }
+
+            ui.add_space(5f32);
+
+            Self::speed_combo_box(ui, "Speed", &mut config.speed).on_hover_text(
+                "How fast the emulation runs compared to real time. Timers always tick 60 times per emulated second.",
+            );
+
+            Self::speed_combo_box(ui, "Fast Forward Speed", &mut config.fast_forward_speed)
+                .on_hover_text("The speed used while the fast forward key is held down.");
 
             ui.add_space(5f32);
 
@@ -135,9 +377,51 @@ impl Settings {
             if memory_size != memory.size {
                 memory.resize(memory.size);
             }
+
+            if let Some(rom_name) = emulator.config.rom_name() {
+                ui.add_space(5f32);
+                Self::draw_rom_override(ui, emulator, rom_name);
+            }
         });
     }
 
+    fn draw_rom_override(ui: &mut Ui, emulator: &mut Emulator, rom_name: String) {
+        let overridden = emulator.config.rom_overrides.contains_key(&rom_name);
+        let mut rom_only = overridden;
+
+        ui.checkbox(&mut rom_only, format!("Use only for {}", rom_name))
+            .on_hover_text(
+                "Keep these emulation settings for this ROM, without changing the global emulation settings.",
+            );
+
+        if rom_only {
+            let settings = emulator.emulation_settings();
+
+            if !overridden {
+                emulator.config.global_emulation.get_or_insert(settings);
+            }
+
+            emulator.config.rom_overrides.insert(rom_name, settings);
+        } else if overridden {
+            emulator.config.rom_overrides.remove(&rom_name);
+
+            if let Some(global) = emulator.config.global_emulation.take() {
+                emulator.apply_emulation_settings(global);
+            }
+        }
+    }
+
+    fn speed_combo_box(ui: &mut Ui, label: &str, speed: &mut Speed) -> Response {
+        ComboBox::from_label(label)
+            .selected_text(speed.to_string())
+            .show_ui(ui, |ui| {
+                for preset in Speed::PRESETS {
+                    ui.selectable_value(speed, preset, preset.to_string());
+                }
+            })
+            .response
+    }
+
     fn draw_emulation_quirks(&self, ui: &mut Ui) {
         let quirks = &mut self.emulator.borrow_mut().config.quirks;
 
@@ -155,7 +439,10 @@ impl Settings {
     }
 
     fn draw_keybindings(&self, ui: &mut Ui) {
-        let keys = &mut self.emulator.borrow_mut().keypad.keys;
+        let emulator = &mut *self.emulator.borrow_mut();
+        let keypad = &mut emulator.keypad;
+        let keys = &mut keypad.keys;
+        let show_keypad = &mut emulator.config.show_keypad;
 
         ui.collapsing("Keybindings", |ui| {
             for row in 0..4 {
@@ -169,15 +456,403 @@ impl Settings {
                     }
                 });
             }
+
+            ui.add_space(5.0);
+
+            ui.checkbox(show_keypad, "Show On-Screen Keypad")
+                .on_hover_text("Show a keypad which can be clicked or touched to press keys.");
+
+            ui.add_space(5.0);
+
+            ui.horizontal(|ui| {
+                ui.label("Fast Forward");
+                Keybind::new(&mut keypad.hotkeys.fast_forward, Id::from("fast_forward"))
+                    .ui(ui)
+                    .on_hover_text("Hold down to emulate at the fast forward speed.");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Toggle Fullscreen");
+                Keybind::new(
+                    &mut keypad.hotkeys.toggle_fullscreen,
+                    Id::from("toggle_fullscreen"),
+                )
+                .ui(ui);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Screenshot");
+                Keybind::new(&mut keypad.hotkeys.screenshot, Id::from("screenshot")).ui(ui);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Start/Stop Recording");
+                Keybind::new(
+                    &mut keypad.hotkeys.toggle_recording,
+                    Id::from("toggle_recording"),
+                )
+                .ui(ui);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Open Debugger");
+                Keybind::new(&mut keypad.hotkeys.open_debugger, Id::from("open_debugger")).ui(ui);
+            });
         })
         .header_response
-        .on_hover_text("Configure keybindings for the Chip 8 Keypad here.");
+        .on_hover_text("Configure keybindings for the Chip 8 Keypad and emulator hotkeys here.");
     }
 
-    fn draw_other_settings(&self, ui: &mut Ui) {
-        let beeper = &mut self.emulator.borrow_mut().beeper;
+    fn draw_palette_settings(&mut self, ui: &mut Ui) {
+        let config = &mut self.emulator.borrow_mut().config;
+
+        ui.collapsing("Colors", |ui| {
+            let palette = config.active_palette_mut();
+
+            ComboBox::from_label("Palette")
+                .selected_text(palette.preset_name().unwrap_or("Custom"))
+                .show_ui(ui, |ui| {
+                    for (name, preset) in Palette::PRESETS {
+                        ui.selectable_value(palette, preset, name);
+                    }
+                });
+
+            ui.add_space(5.0);
+
+            ui.horizontal(|ui| {
+                let names = ["Background", "Plane 1", "Plane 2", "Both Planes"];
+
+                for (name, color) in names.iter().zip(palette.colors.iter_mut()) {
+                    color_picker::color_edit_button_srgba(ui, color, Alpha::Opaque)
+                        .on_hover_text(*name);
+                }
+            });
+
+            ui.add_space(5.0);
+
+            if let Some(rom_name) = config.rom_name() {
+                let mut rom_palette = config.rom_palettes.contains_key(&rom_name);
+
+                if ui
+                    .checkbox(&mut rom_palette, format!("Use only for {}", rom_name))
+                    .on_hover_text(
+                        "Use a separate palette for this ROM, instead of the default palette.",
+                    )
+                    .changed()
+                {
+                    if rom_palette {
+                        config.rom_palettes.insert(rom_name, config.palette);
+                    } else {
+                        config.rom_palettes.remove(&rom_name);
+                    }
+                }
+
+                ui.add_space(5.0);
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Import...").clicked() {
+                    if let Some(filepath) = Self::palette_file_dialog().pick_file() {
+                        let result = fs::read_to_string(&filepath)
+                            .map_err(Error::from)
+                            .and_then(|text| text.parse::<Palette>());
+
+                        match result {
+                            Ok(palette) => {
+                                *config.active_palette_mut() = palette;
+                                self.palette_error = None;
+                            }
+                            Err(error) => self.palette_error = Some(error.to_string()),
+                        }
+                    }
+                }
+
+                if ui.button("Export...").clicked() {
+                    if let Some(filepath) = Self::palette_file_dialog().save_file() {
+                        self.palette_error =
+                            fs::write(filepath, config.active_palette().to_string())
+                                .err()
+                                .map(|error| error.to_string());
+                    }
+                }
+            });
+
+            if let Some(error) = &self.palette_error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+        })
+        .header_response
+        .on_hover_text("Configure the colors used to draw the display here.");
+    }
+
+    fn draw_display_settings(&self, ui: &mut Ui) {
+        let config = &mut self.emulator.borrow_mut().config;
+        let scaling = &mut config.scaling;
+        let anti_flicker = &mut config.anti_flicker;
+
+        ui.collapsing("Display Settings", |ui| {
+            ComboBox::from_label("Scaling")
+                .selected_text(scaling.mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        ScalingMode::Stretch,
+                        ScalingMode::KeepAspect,
+                        ScalingMode::Integer,
+                    ] {
+                        ui.selectable_value(&mut scaling.mode, mode, mode.to_string());
+                    }
+                })
+                .response
+                .on_hover_text("Choose how the display is scaled to fit the window.");
+
+            ui.checkbox(&mut scaling.pixel_grid, "Pixel Grid");
+            ui.checkbox(&mut scaling.scanlines, "Scanlines");
+
+            if ui
+                .checkbox(&mut config.fullscreen, "Fullscreen")
+                .on_hover_text("Can also be toggled using the fullscreen key.")
+                .changed()
+            {
+                ui.ctx()
+                    .send_viewport_cmd(ViewportCommand::Fullscreen(config.fullscreen));
+            }
+
+            ui.add_space(5.0);
+
+            ComboBox::from_label("Anti Flicker")
+                .selected_text(anti_flicker.mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        AntiFlickerMode::Off,
+                        AntiFlickerMode::FrameBlending,
+                        AntiFlickerMode::PhosphorDecay,
+                        AntiFlickerMode::WaitForRedraw,
+                    ] {
+                        ui.selectable_value(&mut anti_flicker.mode, mode, mode.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Reduce the flicker caused by sprites being erased and redrawn every frame.",
+                );
+
+            match anti_flicker.mode {
+                AntiFlickerMode::FrameBlending => {
+                    ui.add(
+                        Slider::new(&mut anti_flicker.blend_frames, 2..=8).text("Blended Frames"),
+                    )
+                    .on_hover_text("How many of the most recent frames are blended together.");
+                }
+
+                AntiFlickerMode::PhosphorDecay => {
+                    ui.add(
+                        Slider::new(&mut anti_flicker.persistence, 0f32..=0.95f32)
+                            .text("Persistence"),
+                    )
+                    .on_hover_text(
+                        "How much of its brightness a pixel keeps every frame after turning off.",
+                    );
+                }
+
+                _ => {}
+            }
+        });
+    }
+
+    fn draw_capture_settings(&self, ui: &mut Ui) {
+        let capture = &mut self.emulator.borrow_mut().config.capture;
+
+        ui.collapsing("Screenshots & Recording", |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Folder: {}", capture.folder.display()));
+
+                if ui.button("Select Folder...").clicked() {
+                    if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                        capture.folder = folder;
+                    }
+                }
+            });
+
+            ui.add(Slider::new(&mut capture.scale, 1..=16).text("Scale"))
+                .on_hover_text("Every pixel of the display is saved as a square of this size.");
+
+            ui.add_space(5.0);
+
+            ComboBox::from_label("Recording Format")
+                .selected_text(capture.recording_format.to_string())
+                .show_ui(ui, |ui| {
+                    for format in [RecordingFormat::Gif, RecordingFormat::PngSequence] {
+                        ui.selectable_value(
+                            &mut capture.recording_format,
+                            format,
+                            format.to_string(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text("Recordings are started and stopped using the recording key.");
+
+            ui.checkbox(&mut capture.record_audio, "Record Beeper")
+                .on_hover_text("Save the beeper into a WAV file alongside the recording.");
+        });
+    }
+
+    fn draw_movie_settings(&mut self, ui: &mut Ui) -> Option<Event> {
+        let emulator = &mut *self.emulator.borrow_mut();
+
+        ui.collapsing("Movies", |ui| {
+            let event = match &emulator.movie {
+                Some(movie) if movie.mode == MovieMode::Recording => {
+                    ui.label(format!("Recording movie: {} frames", movie.movie.frames));
+
+                    if ui.button("Stop & Save Movie...").clicked() {
+                        if let Some(filepath) = Self::movie_file_dialog().save_file() {
+                            self.movie_error = movie
+                                .movie
+                                .save(&filepath)
+                                .err()
+                                .map(|error| format!("{:#}", error));
+                            emulator.movie = None;
+                        }
+                    }
+
+                    None
+                }
+
+                Some(movie) => {
+                    ui.label(format!(
+                        "Playing movie: frame {} of {}",
+                        emulator.frames, movie.movie.frames
+                    ));
+
+                    if ui.button("Stop Movie").clicked() {
+                        emulator.movie = None;
+                    }
+
+                    None
+                }
+
+                None => Self::draw_movie_controls(ui, emulator, &mut self.movie_error),
+            };
+
+            if let Some(error) = &self.movie_error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+
+            event
+        })
+        .body_returned
+        .flatten()
+    }
+
+    fn draw_movie_controls(
+        ui: &mut Ui,
+        emulator: &mut Emulator,
+        movie_error: &mut Option<String>,
+    ) -> Option<Event> {
+        if emulator.get_rom().is_none() {
+            ui.label("Select a ROM to record or play movies");
+            return None;
+        }
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Record Movie")
+                .on_hover_text("Restart the ROM and record all keypad inputs from the start.")
+                .clicked()
+            {
+                emulator.reset();
+
+                if let Err(error) = emulator.load_rom() {
+                    return Some(Event::ReportError(error));
+                }
+
+                emulator.movie = Some(MovieSession::record(emulator));
+                *movie_error = None;
+
+                return Some(Event::StartEmulation);
+            }
+
+            if ui
+                .button("Play Movie...")
+                .on_hover_text("Restart the selected ROM and replay the inputs of a movie.")
+                .clicked()
+            {
+                if let Some(filepath) = Self::movie_file_dialog().pick_file() {
+                    let result = Movie::load(&filepath).and_then(|movie| {
+                        movie.apply(emulator)?;
+                        Ok(movie)
+                    });
+
+                    match result {
+                        Ok(movie) => {
+                            emulator.movie = Some(MovieSession::play(movie));
+                            *movie_error = None;
+
+                            return Some(Event::StartEmulation);
+                        }
+                        Err(error) => *movie_error = Some(format!("{:#}", error)),
+                    }
+                }
+            }
+
+            None
+        })
+        .inner
+    }
+
+    fn draw_script_settings(ui: &mut Ui) -> Option<Event> {
+        ui.collapsing("Scripting", |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Run Script...")
+                    .on_hover_text("Run a Rhai script, which can control the emulation.")
+                    .clicked()
+                {
+                    let filepath = rfd::FileDialog::new()
+                        .add_filter("Rhai Script", &["rhai"])
+                        .pick_file();
+
+                    if let Some(filepath) = filepath {
+                        return Some(Event::LoadScript(filepath));
+                    }
+                }
+
+                if ui.button("Stop Script").clicked() {
+                    return Some(Event::StopScript);
+                }
+
+                None
+            })
+            .inner
+        })
+        .body_returned
+        .flatten()
+    }
+
+    fn movie_file_dialog() -> rfd::FileDialog {
+        rfd::FileDialog::new().add_filter("Movie", &["toml"])
+    }
+
+    fn palette_file_dialog() -> rfd::FileDialog {
+        rfd::FileDialog::new().add_filter("Palette", &["txt"])
+    }
+
+    fn draw_cheat_settings(&mut self, ui: &mut Ui) {
+        ui.collapsing("Cheats", |ui| {
+            self.cheats.draw_cheats(ui, &mut self.emulator.borrow_mut());
+        })
+        .header_response
+        .on_hover_text(
+            "Search memory for the variables of a game, like lives, and freeze them with cheats.",
+        );
+    }
+
+    fn draw_other_settings(&mut self, ui: &mut Ui) {
+        let emulator = &mut *self.emulator.borrow_mut();
 
         ui.collapsing("Other Settings", |ui| {
+            let beeper = &mut emulator.beeper;
             let mut volume = beeper.get_volume();
 
             if ui
@@ -186,6 +861,119 @@ impl Settings {
             {
                 beeper.set_volume(volume);
             }
+
+            Self::draw_audio_output(ui, emulator, &mut self.sound_error);
+
+            let mut sound = emulator.config.sound.clone();
+
+            ComboBox::from_label("Beeper Waveform")
+                .selected_text(sound.waveform.to_string())
+                .show_ui(ui, |ui| {
+                    for waveform in Waveform::ALL {
+                        ui.selectable_value(&mut sound.waveform, waveform, waveform.to_string());
+                    }
+                });
+
+            ui.add(
+                Slider::new(&mut sound.frequency, 50f32..=2000f32)
+                    .logarithmic(true)
+                    .suffix(" Hz")
+                    .text("Beeper Frequency"),
+            );
+            ui.add(
+                Slider::new(&mut sound.attack, 0..=100)
+                    .suffix(" ms")
+                    .text("Beeper Attack"),
+            )
+            .on_hover_text("How long the beeper takes to fade in.");
+            ui.add(
+                Slider::new(&mut sound.release, 0..=100)
+                    .suffix(" ms")
+                    .text("Beeper Release"),
+            )
+            .on_hover_text("How long the beeper takes to fade out.");
+
+            ui.horizontal(|ui| {
+                ui.label(match &sound.sample {
+                    Some(filepath) => format!(
+                        "Beeper Sample: {}",
+                        filepath.file_name().unwrap_or_default().to_string_lossy()
+                    ),
+                    None => "Beeper Sample: None".to_string(),
+                })
+                .on_hover_text("A WAV file looped instead of the waveform while beeping.");
+
+                if ui.button("Choose...").clicked() {
+                    if let Some(filepath) = rfd::FileDialog::new()
+                        .add_filter("WAV", &["wav"])
+                        .pick_file()
+                    {
+                        sound.sample = Some(filepath);
+                    }
+                }
+
+                if sound.sample.is_some() && ui.button("Clear").clicked() {
+                    sound.sample = None;
+                }
+            });
+
+            if sound != emulator.config.sound {
+                match Sound::load(&sound) {
+                    Ok(loaded) => {
+                        emulator.beeper.set_sound(loaded);
+                        emulator.config.sound = sound;
+                        self.sound_error = None;
+                    }
+                    Err(error) => self.sound_error = Some(format!("{:#}", error)),
+                }
+            }
+
+            if let Some(error) = &self.sound_error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
         });
     }
+
+    fn draw_audio_output(ui: &mut Ui, emulator: &mut Emulator, error: &mut Option<String>) {
+        let config = &emulator.config.sound;
+        let mut output = config.output;
+        let mut output_file = config.output_file.clone();
+
+        ComboBox::from_label("Beeper Output")
+            .selected_text(output.to_string())
+            .show_ui(ui, |ui| {
+                for option in AudioOutput::ALL {
+                    ui.selectable_value(&mut output, option, option.to_string());
+                }
+            })
+            .response
+            .on_hover_text("Play the beeper on the audio device, not at all, or into a WAV file.");
+
+        if output == AudioOutput::WavFile {
+            ui.horizontal(|ui| {
+                ui.label(format!("Output File: {}", output_file.display()));
+
+                if ui.button("Choose...").clicked() {
+                    if let Some(filepath) = rfd::FileDialog::new()
+                        .add_filter("WAV", &["wav"])
+                        .set_file_name("beeper.wav")
+                        .save_file()
+                    {
+                        output_file = filepath;
+                    }
+                }
+            });
+        }
+
+        if output != config.output || output_file != config.output_file {
+            *error = emulator
+                .beeper
+                .start(output, output_file.clone())
+                .err()
+                .map(|error| format!("{:#}", error));
+
+            emulator.config.sound.output = output;
+            emulator.config.sound.output_file = output_file;
+        }
+    }
 }